@@ -0,0 +1,98 @@
+use serde::{Serialize, Deserialize};
+
+// How the temperature of Annealing falls with the iteration count k.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Cooling{
+    // T_0 * factor^k, factor slightly below 1.
+    Exponential{ factor: f64 },
+    // T_0 * (1 - k / iterations), zero from then on.
+    Linear{ iterations: usize },
+    // T_0 / ln(k + e). Slow enough for the classic convergence results, usually too slow in practice.
+    Logarithmic,
+}
+
+// Annealing downhill simplex (Press et al., Numerical Recipes, section 10.9). Set
+// NelderMeade::annealing to add thermal fluctuations to the comparisons of step(): every
+// iteration the stored vertex values get a positive fluctuation -T ln(u), u uniform in (0, 1],
+// and every new point a negative one, so the simplex now and then accepts an uphill move and
+// can climb out of a local minimum. As the temperature falls the search turns into plain
+// Nelder-Mead. The optimizer keeps the best point ever evaluated, since the simplex itself may
+// leave it behind, and reports that point in the result. Stalling (RestartPolicy::detect_stalls)
+// is then judged by that point too; a slow schedule may need more stall iterations.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Annealing{
+    // On the scale of the differences between function values that should still be crossed.
+    pub initial_temperature: f64,
+    pub cooling: Cooling,
+    // Seed of the fluctuations. The same seed reproduces a run exactly.
+    pub seed: u64,
+}
+
+impl Annealing{
+    pub fn new(initial_temperature: f64, cooling: Cooling) -> Annealing{
+        Annealing{
+            initial_temperature: initial_temperature,
+            cooling: cooling,
+            seed: 0
+        }
+    }
+
+    pub fn temperature(&self, iteration: usize) -> f64{
+        let k = iteration as f64;
+        let temperature = match self.cooling{
+            Cooling::Exponential{ factor } => self.initial_temperature * factor.powf(k),
+            Cooling::Linear{ iterations } => self.initial_temperature * (1.0 - k / iterations.max(1) as f64),
+            Cooling::Logarithmic => self.initial_temperature / (k + std::f64::consts::E).ln()
+        };
+        temperature.max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{simplex::Simplex, nelder_meade::NelderMeade, termination::Termination};
+
+    #[test]
+    fn cools_down(){
+        let exponential = Annealing::new(2.0, Cooling::Exponential{ factor: 0.5 });
+        assert_eq!(exponential.temperature(0), 2.0);
+        assert_eq!(exponential.temperature(3), 0.25);
+
+        let linear = Annealing::new(2.0, Cooling::Linear{ iterations: 10 });
+        assert_eq!(linear.temperature(5), 1.0);
+        assert_eq!(linear.temperature(10), 0.0);
+        assert_eq!(linear.temperature(20), 0.0);
+
+        let logarithmic = Annealing::new(2.0, Cooling::Logarithmic);
+        assert!((logarithmic.temperature(0) - 2.0).abs() < 1e-15);
+        assert!(logarithmic.temperature(100) < logarithmic.temperature(10));
+    }
+
+    // Many local minima, the smallest at the origin.
+    fn rastrigin(x: &[f64]) -> f64{
+        x.iter().map(|xi| xi * xi - 10.0 * (2.0 * std::f64::consts::PI * xi).cos() + 10.0).sum()
+    }
+
+    fn anneal(seed: u64) -> NelderMeade<fn(&[f64]) -> f64>{
+        let mut func: fn(&[f64]) -> f64 = rastrigin;
+        let simplex = Simplex::from_guess(vec![3.0, -2.0].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        let mut nelder_meade = NelderMeade::new(simplex, func);
+        nelder_meade.annealing = Some(Annealing{ initial_temperature: 20.0, cooling: Cooling::Exponential{ factor: 0.99 }, seed: seed });
+        nelder_meade
+    }
+
+    #[test]
+    fn reports_the_best_point_ever_and_repeats_with_the_same_seed(){
+        let criteria = Termination::new().max_iterations(2_000);
+        let mut first = anneal(7);
+        let result = first.run(&criteria);
+        let best = first.simplex.best().value;
+        assert_eq!(result.best.value, first.best_ever().unwrap().value);
+        assert!(result.best.value <= best);
+
+        let repeated = anneal(7).run(&criteria);
+        assert_eq!(repeated.best.x, result.best.x);
+        assert_eq!(repeated.evaluations, result.evaluations);
+    }
+}
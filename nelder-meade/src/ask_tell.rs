@@ -0,0 +1,237 @@
+use std::time::Instant;
+
+use crate::{simplex::{Simplex, check_vertices}, error::Error, initial::InitialSimplex, point::Point, coefficients::Coefficients, bounds::Bounds, objective::sanitize, nelder_meade::{NelderMeade, Move, Pending}, result::MoveCounts, termination::{Termination, StopReason}};
+
+// Nelder-Mead as a state machine that never calls the objective itself. ask() hands out the
+// points that need a value, tell() takes their values (same order) and advances the search:
+//
+//     let mut search = AskTell::from_guess(guess, 0.5, None)?;
+//     while search.check(&criteria).is_none(){
+//         let points = search.ask();
+//         let values = evaluate_somewhere_else(&points);
+//         search.tell(&values)?;
+//     }
+//
+// Asking again before telling returns the same points. The steps are those of
+// NelderMeade::step, so told the same values AskTell moves the simplex exactly like NelderMeade.
+pub struct AskTell{
+    search: NelderMeade<fn(&[f64]) -> f64>,
+    // None while the initial simplex waits for its values.
+    pending: Option<Pending>,
+    start: Instant,
+}
+
+// The objective of the search, which is never called: every value comes from tell().
+fn told(_: &[f64]) -> f64{
+    f64::INFINITY
+}
+
+impl AskTell{
+    // vertices: the n + 1 points of the initial simplex, still to be evaluated.
+    pub fn new(vertices: Vec<Box<[f64]>>, coefficients: Coefficients) -> Result<AskTell, Error>{
+        let vertex_slices: Vec<&[f64]> = vertices.iter().map(|p| &p[..]).collect();
+        check_vertices(&vertex_slices)?;
+        let points: Box<[Point]> = vertices.into_iter().map(|x| Point{x: x, value: f64::INFINITY}).collect();
+        let mut search = NelderMeade::with_coefficients(Simplex{points: points}, told as fn(&[f64]) -> f64, coefficients);
+        search.evaluations = 0;
+        Ok(AskTell{
+            search: search,
+            pending: None,
+            start: Instant::now()
+        })
+    }
+
+    // Same initial simplex as Simplex::from_guess, with the standard coefficients of
+    // NelderMeade::new.
+    pub fn from_guess(point: Box<[f64]>, step: f64, bounds: Option<&Bounds>) -> Result<AskTell, Error>{
+        AskTell::initial(point, &InitialSimplex::Shrinking(step), bounds)
+    }
+
+    // Starting simplex chosen by initial (see InitialSimplex), with the standard coefficients.
+    pub fn initial(point: Box<[f64]>, initial: &InitialSimplex, bounds: Option<&Bounds>) -> Result<AskTell, Error>{
+        let vertices = initial.vertices(point, bounds)?;
+        let mut ask_tell = AskTell::new(vertices, Coefficients::standard())?;
+        ask_tell.set_bounds(bounds.cloned())?;
+        Ok(ask_tell)
+    }
+
+    // Continues from an already evaluated simplex. Its vertices are checked like those of new(),
+    // and at least one of them needs a finite value.
+    pub fn from_simplex(mut simplex: Simplex, coefficients: Coefficients) -> Result<AskTell, Error>{
+        let vertex_slices: Vec<&[f64]> = simplex.points.iter().map(|p| &p.x[..]).collect();
+        check_vertices(&vertex_slices)?;
+        for point in simplex.points.iter_mut(){
+            point.value = sanitize(point.value);
+        }
+        if simplex.points.iter().all(|p| p.value == f64::INFINITY){
+            return Err(Error::NonFiniteStartValue);
+        }
+        let mut search = NelderMeade::with_coefficients(simplex, told as fn(&[f64]) -> f64, coefficients);
+        search.evaluations = 0;
+        let pending = search.start_step();
+        Ok(AskTell{
+            search: search,
+            pending: Some(pending),
+            start: Instant::now()
+        })
+    }
+
+    pub fn coefficients(&self) -> Coefficients{
+        self.search.coefficients
+    }
+
+    // Takes effect from the next point on.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients){
+        self.search.coefficients = coefficients;
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.search.bounds()
+    }
+
+    // Keeps every new point inside the box, see NelderMeade::set_bounds.
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) -> Result<(), Error>{
+        self.search.set_bounds(bounds)
+    }
+
+    pub fn iterations(&self) -> usize{
+        self.search.iterations
+    }
+
+    // The number of values told so far.
+    pub fn evaluations(&self) -> usize{
+        self.search.evaluations
+    }
+
+    pub fn moves(&self) -> MoveCounts{
+        self.search.moves
+    }
+
+    // None until the initial simplex has been told.
+    pub fn simplex(&self) -> Option<&Simplex>{
+        self.pending.map(|_| &self.search.simplex)
+    }
+
+    pub fn best(&self) -> Option<&Point>{
+        self.simplex().map(|simplex| simplex.best())
+    }
+
+    pub fn ask(&self) -> Vec<Box<[f64]>>{
+        match self.pending{
+            Some(pending) => self.search.pending_points(pending),
+            None => self.search.simplex.points.iter().map(|p| p.x.clone()).collect()
+        }
+    }
+
+    // values must line up with the points of the last ask(). Returns the move once an
+    // iteration is complete, None while the iteration still needs more points.
+    pub fn tell(&mut self, values: &[f64]) -> Result<Option<Move>, Error>{
+        let expected = match self.pending{
+            None => self.search.simplex.points.len(),
+            Some(Pending::Shrink) => self.search.simplex.points.len() - 1,
+            Some(_) => 1
+        };
+        if values.len() != expected{
+            return Err(Error::DimensionMismatch{ expected: expected, found: values.len() });
+        }
+
+        let pending = match self.pending{
+            Some(pending) => pending,
+            None => {
+                for (point, value) in self.search.simplex.points.iter_mut().zip(values){
+                    point.value = sanitize(*value);
+                }
+                self.search.evaluations += values.len();
+                self.pending = Some(self.search.start_step());
+                return Ok(None);
+            }
+        };
+        match self.search.continue_step(pending, values){
+            Ok(taken) => {
+                self.search.iterations += 1;
+                self.pending = Some(self.search.start_step());
+                Ok(Some(taken))
+            },
+            Err(next) => {
+                self.pending = Some(next);
+                Ok(None)
+            }
+        }
+    }
+
+    // Counters are measured from the creation of the AskTell.
+    pub fn check(&self, criteria: &Termination) -> Option<StopReason>{
+        self.simplex().and_then(|simplex| criteria.check(simplex, self.iterations(), self.evaluations(), self.start))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn rosenbrock(x: &[f64]) -> f64{
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)
+    }
+
+    fn tell_until(ask_tell: &mut AskTell, iterations: usize){
+        while ask_tell.iterations() < iterations{
+            let values: Vec<f64> = ask_tell.ask().iter().map(|x| rosenbrock(x)).collect();
+            ask_tell.tell(&values).unwrap();
+        }
+    }
+
+    #[test]
+    fn moves_like_nelder_meade(){
+        let bounds = Bounds::new(vec![-2.0, -0.5], vec![0.5, 2.0]).unwrap();
+        for bounds in [None, Some(bounds)]{
+            let guess: Box<[f64]> = vec![-1.2, 1.0].into_boxed_slice();
+            let mut ask_tell = AskTell::from_guess(guess.clone(), 0.5, bounds.as_ref()).unwrap();
+            tell_until(&mut ask_tell, 300);
+
+            let mut func = rosenbrock;
+            let simplex = Simplex::from_guess(guess, 0.5, bounds.as_ref(), &mut func).unwrap();
+            let mut nelder_meade = NelderMeade::new(simplex, func);
+            nelder_meade.set_bounds(bounds).unwrap();
+            nelder_meade.iterate_n_times(300);
+
+            let simplex = ask_tell.simplex().unwrap();
+            for (told, evaluated) in simplex.points.iter().zip(nelder_meade.simplex.points.iter()){
+                assert_eq!(told.x, evaluated.x);
+                assert_eq!(told.value.to_bits(), evaluated.value.to_bits());
+            }
+            assert_eq!(ask_tell.evaluations(), nelder_meade.evaluations);
+            assert_eq!(ask_tell.moves(), nelder_meade.moves);
+        }
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_count(){
+        let mut ask_tell = AskTell::from_guess(vec![-1.2, 1.0].into_boxed_slice(), 0.5, None).unwrap();
+        assert_eq!(ask_tell.tell(&[1.0]), Err(Error::DimensionMismatch{ expected: 3, found: 1 }));
+        assert!(ask_tell.simplex().is_none());
+
+        tell_until(&mut ask_tell, 1);
+        let evaluations = ask_tell.evaluations();
+        assert_eq!(ask_tell.ask().len(), 1);
+        assert_eq!(ask_tell.tell(&[1.0, 2.0]), Err(Error::DimensionMismatch{ expected: 1, found: 2 }));
+        assert_eq!(ask_tell.evaluations(), evaluations);
+    }
+
+    #[test]
+    fn checks_a_simplex_to_continue_from(){
+        let simplex = |vertices: Vec<[f64; 2]>, values: [f64; 3]| Simplex{
+            points: vertices.into_iter().zip(values).map(|(x, value)| Point{x: x.to_vec().into_boxed_slice(), value: value}).collect()
+        };
+        let triangle = vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        let coefficients = Coefficients::standard();
+
+        let mut ask_tell = AskTell::from_simplex(simplex(triangle.clone(), [f64::NAN, 2.0, 3.0]), coefficients).unwrap();
+        assert_eq!(ask_tell.simplex().unwrap().points[0].value, f64::INFINITY);
+        assert_eq!(ask_tell.ask().len(), 1);
+        assert!(ask_tell.tell(&[1.0]).is_ok());
+
+        assert_eq!(AskTell::from_simplex(simplex(triangle, [f64::NAN, f64::INFINITY, f64::NAN]), coefficients).err(), Some(Error::NonFiniteStartValue));
+        assert_eq!(AskTell::from_simplex(simplex(vec![[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]], [1.0, 2.0, 3.0]), coefficients).err(), Some(Error::DegenerateSimplex));
+        assert_eq!(AskTell::from_simplex(simplex(vec![[0.0, 0.0], [1.0, f64::NAN], [0.0, 1.0]], [1.0, 2.0, 3.0]), coefficients).err(), Some(Error::NonFiniteStart{ position: 1 }));
+    }
+}
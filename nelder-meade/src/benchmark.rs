@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::{example_functions::{rosenbrock, himmelblau, beale, booth, rastrigin, ackley, powell_singular, wood}, nelder_meade::NelderMeade, simplex::Simplex, rng::Rng, termination::Termination, error::Error};
+
+// A standard test problem with its known global minimum value, one of its global minimizers
+// and the start point that is customary in the literature.
+pub struct Problem{
+    pub name: &'static str,
+    pub func: fn(&[f64]) -> f64,
+    pub minimum: f64,
+    pub minimizer: Vec<f64>,
+    pub start: Vec<f64>,
+    pub step: f64,
+}
+
+pub fn problems() -> Vec<Problem>{
+    vec![
+        Problem{name: "Rosenbrock", func: rosenbrock, minimum: 0.0, minimizer: vec![1.0, 1.0], start: vec![-1.2, 1.0], step: 0.5},
+        Problem{name: "Himmelblau", func: himmelblau, minimum: 0.0, minimizer: vec![3.0, 2.0], start: vec![0.0, 0.0], step: 1.0},
+        Problem{name: "Beale", func: beale, minimum: 0.0, minimizer: vec![3.0, 0.5], start: vec![1.0, 1.0], step: 0.5},
+        Problem{name: "Booth", func: booth, minimum: 0.0, minimizer: vec![1.0, 3.0], start: vec![0.0, 0.0], step: 1.0},
+        Problem{name: "Rastrigin", func: rastrigin, minimum: 0.0, minimizer: vec![0.0, 0.0], start: vec![2.5, 2.5], step: 1.0},
+        Problem{name: "Ackley", func: ackley, minimum: 0.0, minimizer: vec![0.0, 0.0], start: vec![2.0, 2.0], step: 1.0},
+        Problem{name: "Powell singular", func: powell_singular, minimum: 0.0, minimizer: vec![0.0, 0.0, 0.0, 0.0], start: vec![3.0, -1.0, 0.0, 1.0], step: 1.0},
+        Problem{name: "Wood", func: wood, minimum: 0.0, minimizer: vec![1.0, 1.0, 1.0, 1.0], start: vec![-3.0, -1.0, -3.0, -1.0], step: 1.0},
+    ]
+}
+
+pub struct BenchmarkReport{
+    pub name: &'static str,
+    pub trials: usize,
+    // Trials that ended within the tolerance of the known minimum value.
+    pub successes: usize,
+    pub mean_evaluations: f64,
+    pub max_evaluations: usize,
+    pub best_value: f64,
+}
+
+impl BenchmarkReport{
+    pub fn success_rate(&self) -> f64{
+        self.successes as f64 / self.trials as f64
+    }
+}
+
+// Runs every problem `trials` times. The first trial starts from the standard start point, the
+// others from start points jittered by up to one step in every coordinate.
+pub fn run_suite(trials: usize, seed: u64, termination: &Termination, tolerance: f64) -> Result<Vec<BenchmarkReport>, Error>{
+    if trials == 0{
+        return Err(Error::NoTrials);
+    }
+    let mut rng = Rng::new(seed);
+    let mut reports: Vec<BenchmarkReport> = Vec::new();
+    for problem in problems(){
+        let mut successes = 0;
+        let mut total_evaluations = 0;
+        let mut max_evaluations = 0;
+        let mut best_value = f64::INFINITY;
+        for trial in 0..trials{
+            let start: Box<[f64]> = problem.start.iter()
+                .map(|x| if trial == 0 { *x } else { x + problem.step * (2.0 * rng.next_f64() - 1.0) })
+                .collect();
+            let mut func = problem.func;
+            let simplex = Simplex::from_guess(start, problem.step, None, &mut func)?;
+            let mut nelder_meade = NelderMeade::adaptive(simplex, func);
+            let result = nelder_meade.run(termination);
+            if (result.best.value - problem.minimum).abs() <= tolerance{
+                successes += 1;
+            }
+            total_evaluations += result.evaluations;
+            max_evaluations = max_evaluations.max(result.evaluations);
+            best_value = best_value.min(result.best.value);
+        }
+        reports.push(BenchmarkReport{
+            name: problem.name,
+            trials: trials,
+            successes: successes,
+            mean_evaluations: total_evaluations as f64 / trials as f64,
+            max_evaluations: max_evaluations,
+            best_value: best_value
+        });
+    }
+    Ok(reports)
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{:<16} success {:>3}/{:<3} ({:>5.1}%)  evaluations mean {:>8.1} max {:>6}  best {:e}",
+            self.name, self.successes, self.trials, 100.0 * self.success_rate(), self.mean_evaluations, self.max_evaluations, self.best_value)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn solves_the_smooth_problems(){
+        let reports = run_suite(5, 1, &Termination::new().max_iterations(10_000).converged(1e-12, 1e-8), 1e-6).unwrap();
+        assert_eq!(reports.len(), problems().len());
+        for (report, problem) in reports.iter().zip(problems()){
+            assert_eq!(report.trials, 5);
+            // a trial succeeded exactly if the best of them ended within the tolerance
+            assert_eq!(report.successes > 0, (report.best_value - problem.minimum).abs() <= 1e-6, "{}", report);
+            assert!(report.mean_evaluations <= report.max_evaluations as f64, "{}", report);
+        }
+        for name in ["Rosenbrock", "Booth", "Beale"]{
+            let report = reports.iter().find(|r| r.name == name).unwrap();
+            assert_eq!(report.successes, 5, "{}", report);
+        }
+    }
+
+    #[test]
+    fn minimizers_attain_the_minimum(){
+        for problem in problems(){
+            assert!(((problem.func)(&problem.minimizer) - problem.minimum).abs() < 1e-12, "{}", problem.name);
+            assert_eq!(problem.minimizer.len(), problem.start.len());
+        }
+    }
+
+    #[test]
+    fn rejects_zero_trials(){
+        assert_eq!(run_suite(0, 0, &Termination::default(), 1e-6).err(), Some(Error::NoTrials));
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{real::{Real, cast}, error::Error};
+
+// Per-coordinate box constraints. Use f64::NEG_INFINITY / f64::INFINITY for
+// coordinates that are only bounded on one side (or not at all).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bounds{
+    #[serde(with = "crate::serde_float::slice")]
+    pub lower: Box<[f64]>,
+    #[serde(with = "crate::serde_float::slice")]
+    pub upper: Box<[f64]>,
+}
+
+impl Bounds{
+    pub fn new(lower: Vec<f64>, upper: Vec<f64>) -> Result<Bounds, Error>{
+        if lower.len() != upper.len(){
+            return Err(Error::DimensionMismatch{ expected: lower.len(), found: upper.len() });
+        }
+        for i in 0..lower.len(){
+            if lower[i].is_nan() || upper[i].is_nan() || lower[i] > upper[i]{
+                return Err(Error::InvalidBounds{ position: i });
+            }
+        }
+        Ok(Bounds{
+            lower: lower.into_boxed_slice(),
+            upper: upper.into_boxed_slice()
+        })
+    }
+
+    pub fn dimensions(&self) -> usize{
+        self.lower.len()
+    }
+
+    pub fn contains(&self, point: &[f64]) -> bool{
+        point.len() == self.dimensions() && 
+            (0..point.len()).all(|i| self.lower[i] <= point[i] && point[i] <= self.upper[i])
+    }
+
+    // Moves every coordinate that lies outside the box onto the nearest face.
+    pub fn project<T: Real>(&self, mut point: Box<[T]>) -> Result<Box<[T]>, Error>{
+        if point.len() != self.dimensions(){
+            return Err(Error::DimensionMismatch{ expected: self.dimensions(), found: point.len() });
+        }
+        self.clamp(&mut point);
+        Ok(point)
+    }
+
+    // Same as project, in place. The point must have the dimension of the box; the optimizers
+    // check that when the bounds are set.
+    pub(crate) fn clamp<T: Real>(&self, point: &mut [T]){
+        assert_eq!(point.len(), self.dimensions(), "point and bounds differ in dimension");
+        for (i, x) in point.iter_mut().enumerate(){
+            *x = x.max(cast(self.lower[i])).min(cast(self.upper[i]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{simplex::Simplex, nelder_meade::NelderMeade, termination::Termination};
+
+    fn sphere(x: &[f64]) -> f64{
+        x.iter().map(|xi| xi * xi).sum()
+    }
+
+    #[test]
+    fn rejects_invalid_bounds(){
+        assert_eq!(Bounds::new(vec![0.0, 2.0], vec![1.0, 1.0]).err(), Some(Error::InvalidBounds{ position: 1 }));
+        assert_eq!(Bounds::new(vec![f64::NAN], vec![1.0]).err(), Some(Error::InvalidBounds{ position: 0 }));
+        assert_eq!(Bounds::new(vec![0.0, 0.0], vec![1.0]).err(), Some(Error::DimensionMismatch{ expected: 2, found: 1 }));
+    }
+
+    #[test]
+    fn projects_onto_the_box(){
+        let bounds = Bounds::new(vec![0.0, f64::NEG_INFINITY], vec![1.0, 2.0]).unwrap();
+        assert_eq!(&*bounds.project(vec![-3.0, 5.0].into_boxed_slice()).unwrap(), &[0.0, 2.0]);
+        assert_eq!(&*bounds.project(vec![0.5, -1e300].into_boxed_slice()).unwrap(), &[0.5, -1e300]);
+        assert_eq!(bounds.project(vec![0.5].into_boxed_slice()).err(), Some(Error::DimensionMismatch{ expected: 2, found: 1 }));
+        assert!(bounds.contains(&[1.0, 2.0]));
+        assert!(!bounds.contains(&[1.0]));
+    }
+
+    #[test]
+    fn rejects_bounds_of_another_dimension(){
+        let bounds = Bounds::new(vec![-1.0; 3], vec![1.0; 3]).unwrap();
+        let mut func = sphere;
+        let guess = vec![0.5, 0.5].into_boxed_slice();
+        assert_eq!(Simplex::from_guess(guess.clone(), 0.1, Some(&bounds), &mut func).err(), Some(Error::DimensionMismatch{ expected: 2, found: 3 }));
+
+        let simplex = Simplex::from_guess(guess, 0.1, None, &mut func).unwrap();
+        let mut nelder_meade = NelderMeade::new(simplex, sphere);
+        assert_eq!(nelder_meade.set_bounds(Some(bounds)), Err(Error::DimensionMismatch{ expected: 2, found: 3 }));
+        assert!(nelder_meade.bounds().is_none());
+    }
+
+    #[test]
+    fn keeps_the_search_inside_the_box(){
+        let bounds = Bounds::new(vec![0.5, -1.0], vec![2.0, 1.0]).unwrap();
+        let mut func = sphere;
+        let simplex = Simplex::from_guess(vec![1.5, 0.5].into_boxed_slice(), 0.4, Some(&bounds), &mut func).unwrap();
+        let mut nelder_meade = NelderMeade::new(simplex, sphere);
+        nelder_meade.set_bounds(Some(bounds.clone())).unwrap();
+        let result = nelder_meade.run(&Termination::new().max_iterations(2_000).x_tol(1e-10));
+        assert!(nelder_meade.simplex.points.iter().all(|p| bounds.contains(&p.x)));
+        assert!((result.best.x[0] - 0.5).abs() < 1e-8 && result.best.x[1].abs() < 1e-6, "{:?}", result.best.x);
+    }
+
+    #[test]
+    #[should_panic]
+    fn clamp_refuses_points_of_another_dimension(){
+        let bounds = Bounds::new(vec![0.0; 3], vec![1.0; 3]).unwrap();
+        bounds.clamp(&mut [2.0, 2.0]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{fs, path::Path};
+
+use serde::{Serialize, Deserialize};
+
+use crate::{simplex::Simplex, real::Real, error::Error, coefficients::Coefficients, bounds::Bounds, result::MoveCounts, restart::RestartPolicy, noise::{NoisePolicy, Samples}, annealing::Annealing, point::Point, rng::Rng};
+
+// Everything needed to continue a NelderMeade run except the objective itself, in the
+// precision of the run. Create with NelderMeade::checkpoint and resume with
+// NelderMeade::from_checkpoint.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Real")]
+pub struct Checkpoint<T = f64>{
+    // The vertices whatever they are stored in, see Vertices::from_simplex.
+    pub simplex: Simplex<T>,
+    pub coefficients: Coefficients,
+    pub bounds: Option<Bounds>,
+    pub iterations: usize,
+    pub evaluations: usize,
+    pub moves: MoveCounts,
+    pub record_history: bool,
+    pub restart: RestartPolicy,
+    pub restarts: usize,
+    #[serde(with = "crate::serde_float")]
+    pub initial_step: T,
+    // The running vertex sum of step() and the vertex replacements since it was recomputed, so
+    // that a run continued with step() adds up exactly like the original. Recomputed from the
+    // simplex when missing.
+    #[serde(default)]
+    pub vertex_sum: Vec<T>,
+    #[serde(default)]
+    pub sum_updates: usize,
+    #[serde(default)]
+    pub noise: Option<NoisePolicy>,
+    // Evaluations behind every vertex value, with a noise policy.
+    #[serde(default)]
+    pub samples: Vec<Samples<T>>,
+    #[serde(default)]
+    pub annealing: Option<Annealing>,
+    // Generator of the thermal fluctuations and best point so far, with annealing.
+    #[serde(default)]
+    pub annealing_rng: Option<Rng>,
+    #[serde(default)]
+    pub best_ever: Option<Point<T>>,
+}
+
+impl<T: Real> Checkpoint<T>{
+    pub fn to_json(&self) -> Result<String, Error>{
+        serde_json::to_string_pretty(self).map_err(|e| Error::Serialization(format!("Could not serialize checkpoint: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<Checkpoint<T>, Error>{
+        serde_json::from_str(json).map_err(|e| Error::Serialization(format!("Could not read checkpoint: {}", e)))
+    }
+
+    // Compact binary form (bincode). Much smaller and faster than JSON for large simplices.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error>{
+        bincode::serialize(self).map_err(|e| Error::Serialization(format!("Could not serialize checkpoint: {}", e)))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Checkpoint<T>, Error>{
+        bincode::deserialize(bytes).map_err(|e| Error::Serialization(format!("Could not read checkpoint: {}", e)))
+    }
+
+    // Writes to a temporary file first and renames it, so a crash while saving never leaves a
+    // truncated checkpoint behind.
+    fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Error>{
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, contents).map_err(|e| Error::Io(format!("Could not write {}: {}", path.display(), e)))?;
+        fs::rename(&temporary, path).map_err(|e| Error::Io(format!("Could not write {}: {}", path.display(), e)))
+    }
+
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), Error>{
+        Self::write_atomically(path.as_ref(), self.to_json()?.as_bytes())
+    }
+
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Checkpoint<T>, Error>{
+        let json = fs::read_to_string(path.as_ref()).map_err(|e| Error::Io(format!("Could not read {}: {}", path.as_ref().display(), e)))?;
+        Self::from_json(&json)
+    }
+
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<(), Error>{
+        Self::write_atomically(path.as_ref(), &self.to_bytes()?)
+    }
+
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Checkpoint<T>, Error>{
+        let bytes = fs::read(path.as_ref()).map_err(|e| Error::Io(format!("Could not read {}: {}", path.as_ref().display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use num_traits::ToPrimitive;
+
+    use crate::{nelder_meade::NelderMeade, simplex::{Vertices, FixedSimplex}, annealing::Cooling, noise::NoisePolicy};
+
+    fn rosenbrock<T: Real>(x: &[T]) -> T{
+        let hundred: T = crate::real::cast(100.0);
+        x.windows(2).fold(T::zero(), |sum, w| sum + (T::one() - w[0]).powi(2) + hundred * (w[1] - w[0] * w[0]).powi(2))
+    }
+
+    fn assert_same_vertices<S: Vertices>(a: &S, b: &S){
+        for i in 0..a.vertex_count(){
+            let (a, b) = (a.point(i), b.point(i));
+            assert_eq!(a.value.to_f64().unwrap().to_bits(), b.value.to_f64().unwrap().to_bits());
+            for (a, b) in a.x.iter().zip(b.x.iter()){
+                assert_eq!(a.to_f64().unwrap().to_bits(), b.to_f64().unwrap().to_bits());
+            }
+        }
+    }
+
+    // Runs 200 iterations straight and 100 + 100 with a checkpoint passed through round_trip in
+    // between, which must end in the same simplex bit for bit.
+    fn resumes_exactly<S: Vertices + Clone>(simplex: S, round_trip: fn(&Checkpoint<S::Scalar>) -> Checkpoint<S::Scalar>){
+        let mut straight = NelderMeade::adaptive(simplex, rosenbrock::<S::Scalar>);
+        straight.annealing = Some(Annealing::new(0.5, Cooling::Exponential{ factor: 0.9 }));
+        straight.iterate_n_times(100);
+        let checkpoint = round_trip(&straight.checkpoint());
+        straight.iterate_n_times(100);
+
+        let mut resumed: NelderMeade<_, S> = NelderMeade::from_checkpoint(checkpoint, rosenbrock::<S::Scalar>).unwrap();
+        resumed.iterate_n_times(100);
+        assert_same_vertices(&straight.simplex, &resumed.simplex);
+        assert_eq!(straight.iterations, resumed.iterations);
+        assert_eq!(straight.evaluations, resumed.evaluations);
+        assert_eq!(straight.best_ever().map(|p| p.value), resumed.best_ever().map(|p| p.value));
+    }
+
+    fn through_json<T: Real>(checkpoint: &Checkpoint<T>) -> Checkpoint<T>{
+        Checkpoint::from_json(&checkpoint.to_json().unwrap()).unwrap()
+    }
+
+    fn through_bytes<T: Real>(checkpoint: &Checkpoint<T>) -> Checkpoint<T>{
+        Checkpoint::from_bytes(&checkpoint.to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn resumes_exactly_from_json_and_bincode(){
+        let mut func = rosenbrock::<f64>;
+        let simplex = Simplex::from_guess(vec![-1.2, 1.0, -0.7, 0.3].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        resumes_exactly(simplex.clone(), through_json);
+        resumes_exactly(simplex, through_bytes);
+    }
+
+    #[test]
+    fn resumes_f32_and_fixed_simplices(){
+        let mut func = rosenbrock::<f32>;
+        let simplex = Simplex::from_guess(vec![-1.2f32, 1.0, -0.7, 0.3].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        resumes_exactly(simplex.clone(), through_json);
+        resumes_exactly(simplex, through_bytes);
+
+        let mut func = rosenbrock::<f64>;
+        let fixed = FixedSimplex::from_guess([-1.2, 1.0, -0.7, 0.3], 0.5, None, &mut func).unwrap();
+        resumes_exactly(fixed, through_json);
+        resumes_exactly(fixed, through_bytes);
+    }
+
+    #[test]
+    fn resumes_exactly_with_step(){
+        let steps = |nelder_meade: &mut NelderMeade<fn(&[f64]) -> f64>, n: usize| for _ in 0..n{
+            let needed_indices = nelder_meade.needed_points();
+            nelder_meade.step(&needed_indices);
+        };
+        let mut func = rosenbrock::<f64>;
+        let simplex = Simplex::from_guess(vec![-1.2, 1.0, -0.7, 0.3].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        let mut straight = NelderMeade::adaptive(simplex, rosenbrock::<f64> as fn(&[f64]) -> f64);
+        for n in [1, 2, 3, 4, 5]{
+            steps(&mut straight, n);
+            let checkpoint = through_json(&straight.checkpoint());
+            steps(&mut straight, 50);
+
+            let mut resumed = NelderMeade::from_checkpoint(checkpoint, rosenbrock::<f64> as fn(&[f64]) -> f64).unwrap();
+            steps(&mut resumed, 50);
+            assert_same_vertices(&straight.simplex, &resumed.simplex);
+        }
+    }
+
+    #[test]
+    fn keeps_the_noise_samples(){
+        let mut func = rosenbrock::<f64>;
+        let simplex = Simplex::from_guess(vec![-1.2, 1.0, -0.7, 0.3].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        let mut nelder_meade = NelderMeade::adaptive(simplex, rosenbrock::<f64>);
+        nelder_meade.noise = Some(NoisePolicy::new(3, 64).unwrap());
+        nelder_meade.iterate_n_times(10);
+        let checkpoint = through_json(&nelder_meade.checkpoint());
+        assert_eq!(checkpoint.samples.len(), 5);
+        assert!(checkpoint.samples.iter().all(|s| s.count >= 1));
+    }
+
+    #[test]
+    fn rejects_a_simplex_of_another_dimension(){
+        let mut func = rosenbrock::<f64>;
+        let simplex = Simplex::from_guess(vec![-1.2, 1.0, -0.7, 0.3].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        let checkpoint = NelderMeade::adaptive(simplex, rosenbrock::<f64>).checkpoint();
+        let resumed: Result<NelderMeade<_, FixedSimplex<3>>, Error> = NelderMeade::from_checkpoint(checkpoint, |x: &[f64]| x[0]);
+        assert_eq!(resumed.err(), Some(Error::DimensionMismatch{ expected: 4, found: 5 }));
+    }
+
+    #[test]
+    fn json_keeps_every_bit(){
+        let mut rng = Rng::new(7);
+        let points: Box<[Point]> = (0..200).map(|_| Point{
+            x: vec![rng.next_f64() * 1e3 - 5e2, rng.next_f64() * 1e-7].into_boxed_slice(),
+            value: rng.next_f64() / 3.0
+        }).collect();
+        let simplex = Simplex{points: points};
+        let json = serde_json::to_string(&simplex).unwrap();
+        let read: Simplex = serde_json::from_str(&json).unwrap();
+        for (a, b) in simplex.points.iter().zip(read.points.iter()){
+            assert_eq!(a.value.to_bits(), b.value.to_bits());
+            assert!(a.x.iter().zip(b.x.iter()).all(|(a, b)| a.to_bits() == b.to_bits()));
+        }
+    }
+}
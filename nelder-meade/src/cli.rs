@@ -0,0 +1,243 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{error::Error, initial::InitialSimplex, coefficients::Coefficients, termination::Termination, result::OptimizationResult, expression::Expression, simplex::Simplex, bounds::Bounds, nelder_meade::NelderMeade, restart::RestartPolicy};
+
+pub const USAGE: &str = "Usage: nelder-meade <EXPRESSION> [OPTIONS]
+
+Minimizes EXPRESSION, a formula over the variables x0, x1, ..., for example
+    nelder-meade \"(1-x0)^2 + 100*(x1-x0^2)^2\" --start -1.2,1
+
+Supported: + - * / ^, parentheses, numbers, pi, e and the functions
+sin cos tan exp ln log sqrt abs.
+
+Options:
+    --start X0,X1,...         start point (default: all zeros)
+    --step STEP               initial simplex size (default: 1)
+    --initial KIND            initial simplex: shrinking (default), axis, regular
+                              (edges of length STEP) or pfeffer (5% of every
+                              coordinate, ignores STEP)
+    --steps S0,S1,...         axis simplex with a separate step per coordinate
+    --coefficients MODE       standard, adaptive (default) or four numbers
+                              REFLECTION,EXPANSION,CONTRACTION,SHRINK
+    --lower L0,L1,...         lower bounds
+    --upper U0,U1,...         upper bounds
+    --f-tol TOL               stop when the spread of simplex values is below TOL
+    --x-tol TOL               stop when the simplex diameter is below TOL
+    --max-iterations N        stop after N iterations
+    --max-evaluations N       stop after N function evaluations
+    --time-limit SECONDS      stop after SECONDS of wall-clock time
+    --restarts N              restarts allowed when the simplex stagnates or
+                              degenerates (default: 5)
+    --format text|json        output format (default: text)
+    --help                    show this message
+
+Without any stopping option the run stops after 10000 iterations or once the
+spread of simplex values is below 1e-10 and the simplex diameter below 1e-8.";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format{
+    Text,
+    Json,
+}
+
+pub struct Options{
+    pub expression: String,
+    pub start: Option<Vec<f64>>,
+    pub initial: InitialSimplex,
+    // None picks the adaptive coefficients for the problem dimension.
+    pub coefficients: Option<Coefficients>,
+    pub lower: Option<Vec<f64>>,
+    pub upper: Option<Vec<f64>>,
+    pub termination: Termination,
+    pub restarts: usize,
+    pub format: Format,
+    pub help: bool,
+}
+
+fn parse_list(flag: &str, value: &str) -> Result<Vec<f64>, Error>{
+    value.split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|_| Error::Usage(format!("Invalid number '{}' for {}", v, flag))))
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, Error>{
+    value.trim().parse::<T>().map_err(|_| Error::Usage(format!("Invalid value '{}' for {}", value, flag)))
+}
+
+impl Options{
+    pub fn parse(args: &[String]) -> Result<Options, Error>{
+        let mut options = Options{
+            expression: String::new(),
+            start: None,
+            initial: InitialSimplex::Shrinking(1.0),
+            coefficients: None,
+            lower: None,
+            upper: None,
+            termination: Termination::new(),
+            restarts: 5,
+            format: Format::Text,
+            help: false
+        };
+        let mut expression: Option<String> = None;
+        let mut has_criteria = false;
+        let mut step = 1.0;
+        let mut initial: Option<String> = None;
+        let mut steps: Option<Vec<f64>> = None;
+        let mut i = 0;
+        while i < args.len(){
+            let flag = args[i].as_str();
+            if flag == "--help" || flag == "-h"{
+                options.help = true;
+                return Ok(options);
+            }
+            if !flag.starts_with("--"){
+                if expression.is_some(){
+                    return Err(Error::Usage(format!("Unexpected argument '{}'. Quote the expression if it contains spaces", flag)));
+                }
+                expression = Some(flag.to_owned());
+                i += 1;
+                continue;
+            }
+            let value = match args.get(i + 1){
+                Some(value) => value.as_str(),
+                None => return Err(Error::Usage(format!("Missing value for {}", flag)))
+            };
+            match flag{
+                "--start" => options.start = Some(parse_list(flag, value)?),
+                "--step" => step = parse_number(flag, value)?,
+                "--initial" => initial = Some(value.to_owned()),
+                "--steps" => steps = Some(parse_list(flag, value)?),
+                "--coefficients" => options.coefficients = match value{
+                    "adaptive" => None,
+                    "standard" => Some(Coefficients::standard()),
+                    _ => {
+                        let numbers = parse_list(flag, value)?;
+                        if numbers.len() != 4{
+                            return Err(Error::Usage(format!("{} takes standard, adaptive or four numbers, got '{}'", flag, value)));
+                        }
+                        Some(Coefficients{reflection: numbers[0], expansion: numbers[1], contraction: numbers[2], shrink: numbers[3]})
+                    }
+                },
+                "--lower" => options.lower = Some(parse_list(flag, value)?),
+                "--upper" => options.upper = Some(parse_list(flag, value)?),
+                "--f-tol" => options.termination.f_tol = Some(parse_number(flag, value)?),
+                "--x-tol" => options.termination.x_tol = Some(parse_number(flag, value)?),
+                "--max-iterations" => options.termination.max_iterations = Some(parse_number(flag, value)?),
+                "--max-evaluations" => options.termination.max_evaluations = Some(parse_number(flag, value)?),
+                "--time-limit" => options.termination.time_limit = match Duration::try_from_secs_f64(parse_number(flag, value)?){
+                    Ok(limit) => Some(limit),
+                    Err(_) => return Err(Error::Usage(format!("Invalid value '{}' for {}", value, flag)))
+                },
+                "--restarts" => options.restarts = parse_number(flag, value)?,
+                "--format" => options.format = match value{
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    _ => return Err(Error::Usage(format!("Unknown format '{}'. Use text or json", value)))
+                },
+                _ => return Err(Error::Usage(format!("Unknown option '{}'", flag)))
+            }
+            if ["--f-tol", "--x-tol", "--max-iterations", "--max-evaluations", "--time-limit"].contains(&flag){
+                has_criteria = true;
+            }
+            i += 2;
+        }
+        options.expression = match expression{
+            Some(expression) => expression,
+            None => return Err(Error::Usage("Missing expression".to_owned()))
+        };
+        options.initial = match (initial.as_deref(), steps){
+            (Some(_), Some(_)) => return Err(Error::Usage("--steps cannot be combined with --initial".to_owned())),
+            (_, Some(steps)) => InitialSimplex::Steps(steps.into_boxed_slice()),
+            (None, None) | (Some("shrinking"), None) => InitialSimplex::Shrinking(step),
+            (Some("axis"), None) => InitialSimplex::Axis(step),
+            (Some("regular"), None) => InitialSimplex::Regular(step),
+            (Some("pfeffer"), None) => InitialSimplex::pfeffer(),
+            (Some(kind), None) => return Err(Error::Usage(format!("Unknown initial simplex '{}'. Use shrinking, axis, regular or pfeffer", kind)))
+        };
+        if !has_criteria{
+            options.termination = Termination::new().max_iterations(10_000).converged(1e-10, 1e-8);
+        }
+        Ok(options)
+    }
+}
+
+// Parses the expression and runs NelderMeade as the options describe.
+pub fn optimize(options: &Options) -> Result<OptimizationResult, Error>{
+    let expression = Expression::parse(&options.expression)?;
+    let guess: Box<[f64]> = match &options.start{
+        Some(start) => start.clone().into_boxed_slice(),
+        None => vec![0.0; expression.dimensions().max(1)].into_boxed_slice()
+    };
+    if guess.len() < expression.dimensions(){
+        return Err(Error::Usage(format!("The expression uses x{} but the start point only has {} coordinates", expression.dimensions() - 1, guess.len())));
+    }
+    let bounds = match (&options.lower, &options.upper){
+        (None, None) => None,
+        (lower, upper) => {
+            let lower = lower.clone().unwrap_or(vec![f64::NEG_INFINITY; guess.len()]);
+            let upper = upper.clone().unwrap_or(vec![f64::INFINITY; guess.len()]);
+            Some(Bounds::new(lower, upper)?)
+        }
+    };
+
+    let mut objective = |x: &[f64]| expression.evaluate(x);
+    let simplex = Simplex::initial(guess, &options.initial, bounds.as_ref(), &mut objective)?;
+    let mut nelder_meade = match options.coefficients{
+        Some(coefficients) => NelderMeade::with_coefficients(simplex, objective, coefficients),
+        None => NelderMeade::adaptive(simplex, objective)
+    };
+    nelder_meade.set_bounds(bounds)?;
+    nelder_meade.restart = RestartPolicy::new(options.restarts);
+    nelder_meade.restart.detect_stalls = true;
+    Ok(nelder_meade.run(&options.termination))
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a>{
+    expression: &'a str,
+    #[serde(flatten)]
+    result: &'a OptimizationResult,
+}
+
+pub fn result_to_json(expression: &str, result: &OptimizationResult) -> String{
+    serde_json::to_string(&JsonOutput{expression: expression, result: result}).unwrap()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::termination::StopReason;
+
+    fn parse(args: &[&str]) -> Result<Options, Error>{
+        Options::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn default_termination_does_not_stop_on_a_symmetric_start(){
+        let options = parse(&["x0^2+x1^2+x2^2"]).unwrap();
+        let result = optimize(&options).unwrap();
+        assert_eq!(result.stop_reason, StopReason::Converged);
+        assert!(result.iterations > 0);
+        assert!(result.best.value < 1e-10);
+    }
+
+    #[test]
+    fn rejects_invalid_time_limits(){
+        for limit in ["-1", "NaN", "inf"]{
+            assert!(parse(&["x0^2", "--time-limit", limit]).is_err());
+        }
+        assert!(parse(&["x0^2", "--time-limit", "0.5"]).is_ok());
+    }
+
+    #[test]
+    fn reports_typed_errors(){
+        assert!(matches!(parse(&["x0^2", "--frobnicate", "1"]), Err(Error::Usage(_))));
+        assert!(matches!(parse(&[]), Err(Error::Usage(_))));
+        let options = parse(&["x0^2 +* x1"]).unwrap();
+        assert!(matches!(optimize(&options), Err(Error::Syntax{ position: 6, .. })));
+        let options = parse(&["x0^2 + x1", "--start", "1,1", "--lower", "0,0,0"]).unwrap();
+        assert_eq!(optimize(&options).err(), Some(Error::DimensionMismatch{ expected: 3, found: 2 }));
+    }
+}
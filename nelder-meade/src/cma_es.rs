@@ -0,0 +1,452 @@
+use std::{time::Instant, cmp::Ordering};
+
+use crate::{point::Point, objective::{Objective, sanitize}, real::{Real, cast}, bounds::Bounds, error::Error, rng::Rng, optimizer::{Optimizer, evaluate_points, feasible_guess}, termination::{Termination, StopReason}, result::{OptimizationResult, IterationRecord}};
+
+// Covariance matrix adaptation evolution strategy (Hansen & Ostermeier, 2001), following
+// Hansen's tutorial "The CMA Evolution Strategy" (2016). Samples a population from a normal
+// distribution, moves the mean towards the better half and learns the shape of the
+// distribution from the successful steps. Far more robust than NelderMeade on rugged problems
+// and in tens of dimensions.
+//
+// With restarts (IPOP-CMA-ES, Auger & Hansen 2005), a run whose distribution has converged
+// (Termination::f_tol or Termination::x_tol met, or an ill-conditioned covariance) starts
+// over with a larger population, which smooths out more and more local minima. The other
+// criteria end the whole run, and the last generation is cut short to stay within
+// Termination::max_evaluations. The result holds the best point of all restarts.
+//
+// The distribution lives in f64 whatever T is. With bounds, samples are evaluated at their
+// projection onto the box, and among equal values samples closer to the box rank higher.
+pub struct CmaEs<O, T = f64> where O: Objective<T>{
+    pub func: O,
+    // Samples per generation (lambda). None uses 4 + 3 ln(n).
+    pub population: Option<usize>,
+    // Restarts with a larger population. 0 runs plain CMA-ES.
+    pub max_restarts: usize,
+    // Factor the population grows by with every restart.
+    pub population_growth: f64,
+    // Seed of the sampling. The same seed and settings reproduce a run exactly.
+    pub seed: u64,
+    pub iterations: usize,
+    pub evaluations: usize,
+    pub restarts: usize,
+    // Keep a per-iteration record of the best value and largest standard deviation in run().
+    pub record_history: bool,
+    // The guess was projected onto them, see new.
+    bounds: Option<Bounds>,
+    guess: Box<[f64]>,
+    sigma: f64,
+    best: Point<T>,
+    rng: Option<Rng>,
+    state: Option<State>,
+}
+
+// The distribution and learning rates of the current (re)start.
+struct State{
+    dimensions: usize,
+    population: usize,
+    weights: Vec<f64>,
+    mu_eff: f64,
+    c_sigma: f64,
+    d_sigma: f64,
+    c_c: f64,
+    c_1: f64,
+    c_mu: f64,
+    // Expected length of a standard normal vector.
+    chi_n: f64,
+    mean: Vec<f64>,
+    sigma: f64,
+    p_sigma: Vec<f64>,
+    p_c: Vec<f64>,
+    // Row-major n x n. covariance = eigenvectors * diag(scales^2) * eigenvectors^T
+    covariance: Vec<f64>,
+    eigenvectors: Vec<f64>,
+    scales: Vec<f64>,
+    generation: usize,
+    eigen_generation: usize,
+    // Largest minus smallest value of the last generation.
+    spread: f64,
+}
+
+// Covariance matrices whose condition number exceeds this trigger a restart.
+const MAX_CONDITION: f64 = 1e14;
+
+impl State{
+    fn new(mean: Vec<f64>, sigma: f64, population: usize) -> State{
+        let dimensions = mean.len();
+        let n = dimensions as f64;
+        let mu = population / 2;
+        let mut weights: Vec<f64> = (0..mu).map(|i| (mu as f64 + 0.5).ln() - ((i + 1) as f64).ln()).collect();
+        let total: f64 = weights.iter().sum();
+        for w in weights.iter_mut(){
+            *w /= total;
+        }
+        let mu_eff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+        let c_sigma = (mu_eff + 2.0) / (n + mu_eff + 5.0);
+        let c_1 = 2.0 / ((n + 1.3).powi(2) + mu_eff);
+        let mut identity = vec![0.0; dimensions * dimensions];
+        for i in 0..dimensions{
+            identity[i * dimensions + i] = 1.0;
+        }
+        State{
+            dimensions: dimensions,
+            population: population,
+            mu_eff: mu_eff,
+            c_sigma: c_sigma,
+            d_sigma: 1.0 + 2.0 * (((mu_eff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + c_sigma,
+            c_c: (4.0 + mu_eff / n) / (n + 4.0 + 2.0 * mu_eff / n),
+            c_1: c_1,
+            c_mu: (1.0 - c_1).min(2.0 * (mu_eff - 2.0 + 1.0 / mu_eff) / ((n + 2.0).powi(2) + mu_eff)),
+            chi_n: n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n)),
+            weights: weights,
+            mean: mean,
+            sigma: sigma,
+            p_sigma: vec![0.0; dimensions],
+            p_c: vec![0.0; dimensions],
+            covariance: identity.clone(),
+            eigenvectors: identity,
+            scales: vec![1.0; dimensions],
+            generation: 0,
+            eigen_generation: 0,
+            spread: f64::INFINITY
+        }
+    }
+
+    // mean + sigma * B * D * z
+    fn sample(&self, z: &[f64]) -> Vec<f64>{
+        let n = self.dimensions;
+        (0..n).map(|i| self.mean[i] + self.sigma * (0..n).map(|k| self.eigenvectors[i * n + k] * self.scales[k] * z[k]).sum::<f64>()).collect()
+    }
+
+    // Largest standard deviation of the distribution along a coordinate.
+    fn size(&self) -> f64{
+        let n = self.dimensions;
+        self.sigma * (0..n).map(|i| self.covariance[i * n + i]).fold(0.0, f64::max).sqrt()
+    }
+
+    fn condition(&self) -> f64{
+        let largest = self.scales.iter().cloned().fold(0.0, f64::max);
+        let smallest = self.scales.iter().cloned().fold(f64::INFINITY, f64::min);
+        (largest / smallest).powi(2)
+    }
+
+    // Moves the distribution towards the samples, which are sorted best first.
+    fn update(&mut self, sorted: &[Vec<f64>]){
+        let n = self.dimensions;
+        let old_mean = self.mean.clone();
+        for i in 0..n{
+            self.mean[i] = self.weights.iter().zip(sorted).map(|(w, x)| w * x[i]).sum();
+        }
+        let y_w: Vec<f64> = (0..n).map(|i| (self.mean[i] - old_mean[i]) / self.sigma).collect();
+
+        // C^(-1/2) * y_w = B * D^-1 * B^T * y_w
+        let projected: Vec<f64> = (0..n).map(|k| (0..n).map(|i| self.eigenvectors[i * n + k] * y_w[i]).sum::<f64>() / self.scales[k]).collect();
+        let whitened: Vec<f64> = (0..n).map(|i| (0..n).map(|k| self.eigenvectors[i * n + k] * projected[k]).sum()).collect();
+        let sigma_rate = (self.c_sigma * (2.0 - self.c_sigma) * self.mu_eff).sqrt();
+        for (p, w) in self.p_sigma.iter_mut().zip(whitened.iter()){
+            *p = (1.0 - self.c_sigma) * *p + sigma_rate * w;
+        }
+        self.generation += 1;
+        let p_sigma_norm = self.p_sigma.iter().map(|p| p * p).sum::<f64>().sqrt();
+        let outrunning = p_sigma_norm / (1.0 - (1.0 - self.c_sigma).powi(2 * self.generation as i32)).sqrt() >= (1.4 + 2.0 / (n as f64 + 1.0)) * self.chi_n;
+        // stall the update of p_c while p_sigma is much longer than expected, e.g. right after a start
+        let h_sigma = if outrunning { 0.0 } else { 1.0 };
+        let c_rate = (self.c_c * (2.0 - self.c_c) * self.mu_eff).sqrt();
+        for (p, y) in self.p_c.iter_mut().zip(y_w.iter()){
+            *p = (1.0 - self.c_c) * *p + h_sigma * c_rate * y;
+        }
+
+        let steps: Vec<Vec<f64>> = sorted.iter().take(self.weights.len()).map(|x| (0..n).map(|i| (x[i] - old_mean[i]) / self.sigma).collect()).collect();
+        let keep = 1.0 - self.c_1 - self.c_mu + (1.0 - h_sigma) * self.c_1 * self.c_c * (2.0 - self.c_c);
+        for i in 0..n{
+            for j in 0..=i{
+                let rank_mu: f64 = self.weights.iter().zip(steps.iter()).map(|(w, y)| w * y[i] * y[j]).sum();
+                let value = keep * self.covariance[i * n + j] + self.c_1 * self.p_c[i] * self.p_c[j] + self.c_mu * rank_mu;
+                self.covariance[i * n + j] = value;
+                self.covariance[j * n + i] = value;
+            }
+        }
+        self.sigma *= ((self.c_sigma / self.d_sigma) * (p_sigma_norm / self.chi_n - 1.0)).min(1.0).exp();
+
+        // the decomposition is O(n^3), so it lags behind the covariance by a few generations
+        let lag = (1.0 / ((self.c_1 + self.c_mu) * n as f64 * 10.0)).max(1.0) as usize;
+        if self.generation - self.eigen_generation >= lag{
+            self.eigen_generation = self.generation;
+            let (eigenvalues, eigenvectors) = symmetric_eigen(&self.covariance, n);
+            self.eigenvectors = eigenvectors;
+            self.scales = eigenvalues.iter().map(|e| e.max(f64::MIN_POSITIVE).sqrt()).collect();
+        }
+    }
+}
+
+// Eigenvalues and row-major eigenvectors (as columns) of a symmetric n x n matrix, by cyclic
+// Jacobi rotations.
+fn symmetric_eigen(matrix: &[f64], n: usize) -> (Vec<f64>, Vec<f64>){
+    let mut a = matrix.to_vec();
+    let mut v = vec![0.0; n * n];
+    for i in 0..n{
+        v[i * n + i] = 1.0;
+    }
+    for _ in 0..100{
+        let off_diagonal: f64 = (0..n).flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j))).map(|(i, j)| a[i * n + j].powi(2)).sum();
+        let diagonal: f64 = (0..n).map(|i| a[i * n + i].powi(2)).sum();
+        if off_diagonal <= 1e-24 * diagonal{
+            break;
+        }
+        for p in 0..n{
+            for q in (p + 1)..n{
+                let apq = a[p * n + q];
+                if apq == 0.0{
+                    continue;
+                }
+                let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..n{
+                    let akp = a[k * n + p];
+                    let akq = a[k * n + q];
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n{
+                    let apk = a[p * n + k];
+                    let aqk = a[q * n + k];
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n{
+                    let vkp = v[k * n + p];
+                    let vkq = v[k * n + q];
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    ((0..n).map(|i| a[i * n + i]).collect(), v)
+}
+
+impl<O: Objective<T>, T: Real> CmaEs<O, T>{
+
+    // sigma: initial standard deviation, about a quarter of the range the minimum may lie in.
+    // Starts from the guess projected onto the bounds, if any.
+    pub fn new(guess: Box<[T]>, sigma: T, bounds: Option<&Bounds>, mut func: O) -> Result<CmaEs<O, T>, Error>{
+        let guess = feasible_guess(guess, bounds, &func)?;
+        if !sigma.is_finite() || sigma <= T::zero(){
+            return Err(Error::InvalidStep);
+        }
+        let value = sanitize(func.evaluate(&guess));
+        Ok(CmaEs{
+            func: func,
+            bounds: bounds.cloned(),
+            population: None,
+            max_restarts: 0,
+            population_growth: 2.0,
+            seed: 0,
+            iterations: 0,
+            evaluations: 1,
+            restarts: 0,
+            record_history: false,
+            guess: guess.iter().map(|x| x.to_f64().unwrap()).collect(),
+            sigma: sigma.to_f64().unwrap(),
+            best: Point{x: guess, value: value},
+            rng: None,
+            state: None
+        })
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.bounds.as_ref()
+    }
+
+    pub fn best(&self) -> &Point<T>{
+        &self.best
+    }
+
+    fn default_population(&self) -> usize{
+        let population = self.population.unwrap_or(4 + (3.0 * (self.guess.len() as f64).ln()) as usize);
+        population.max(2)
+    }
+
+    // The first start is at the guess. Restarts pick a uniform point in the box when it is
+    // finite and go back to the guess otherwise.
+    fn start(&mut self){
+        let rng = self.rng.get_or_insert_with(|| Rng::new(self.seed));
+        let mean: Vec<f64> = match &self.bounds{
+            Some(bounds) if self.restarts > 0 && bounds.lower.iter().chain(bounds.upper.iter()).all(|b| b.is_finite()) => {
+                (0..bounds.dimensions()).map(|i| bounds.lower[i] + rng.next_f64() * (bounds.upper[i] - bounds.lower[i])).collect()
+            },
+            _ => self.guess.to_vec()
+        };
+        let population = (self.default_population() as f64 * self.population_growth.powi(self.restarts as i32)).round() as usize;
+        self.state = Some(State::new(mean, self.sigma, population));
+    }
+
+    // Samples, evaluates and ranks one generation and updates the distribution.
+    pub fn step(&mut self){
+        self.generation(None);
+    }
+
+    // step() evaluating at most max_evaluations samples. A cut-short generation only updates
+    // the best point, since the ranking of the samples left out is unknown.
+    fn generation(&mut self, max_evaluations: Option<usize>){
+        if self.state.is_none(){
+            self.start();
+        }
+        let state = self.state.as_ref().unwrap();
+        let rng = self.rng.as_mut().unwrap();
+        let n = state.dimensions;
+        let samples: Vec<Vec<f64>> = (0..state.population).map(|_| {
+            let z: Vec<f64> = (0..n).map(|_| rng.normal()).collect();
+            state.sample(&z)
+        }).collect();
+
+        let mut points: Vec<Box<[T]>> = samples.iter().map(|x| x.iter().map(|v| cast(*v)).collect()).collect();
+        let mut outside = vec![0.0; points.len()];
+        if let Some(bounds) = &self.bounds{
+            for (k, point) in points.iter_mut().enumerate(){
+                bounds.clamp(point);
+                outside[k] = samples[k].iter().zip(point.iter()).map(|(s, p)| (s - p.to_f64().unwrap()).powi(2)).sum();
+            }
+        }
+        let evaluated = max_evaluations.map_or(points.len(), |m| m.min(points.len()));
+        let slices: Vec<&[T]> = points[..evaluated].iter().map(|p| &p[..]).collect();
+        let values = evaluate_points(&mut self.func, &mut self.evaluations, &slices);
+
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap_or(Ordering::Equal).then(outside[*a].partial_cmp(&outside[*b]).unwrap_or(Ordering::Equal)));
+        let first = order[0];
+        if values[first] < self.best.value{
+            self.best = Point{x: points[first].clone(), value: values[first]};
+        }
+        if evaluated < points.len(){
+            return;
+        }
+        let sorted: Vec<Vec<f64>> = order.iter().map(|k| samples[*k].clone()).collect();
+        let state = self.state.as_mut().unwrap();
+        state.spread = (values[order[order.len() - 1]] - values[first]).to_f64().unwrap();
+        state.update(&sorted);
+    }
+
+    pub fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+        let start_evaluations = self.evaluations;
+        let mut history: Option<Vec<IterationRecord<T>>> = if self.record_history { Some(Vec::new()) } else { None };
+        let stop_reason: StopReason = loop {
+            let (spread, size, condition) = match &self.state{
+                Some(state) => (state.spread, state.size(), state.condition()),
+                None => (f64::INFINITY, f64::INFINITY, 1.0)
+            };
+            // the budget first, so that a converged run does not restart past it
+            if let Some(reason) = criteria.check_budget(self.iterations - start_iterations, self.evaluations - start_evaluations, start){
+                break reason;
+            }
+            let reason = criteria.check_progress(spread, size, self.iterations - start_iterations, self.evaluations - start_evaluations, start);
+            let converged = matches!(reason, Some(StopReason::FunctionTolerance) | Some(StopReason::StepTolerance) | Some(StopReason::Converged));
+            if converged || condition > MAX_CONDITION{
+                if self.restarts >= self.max_restarts{
+                    break reason.unwrap_or(StopReason::Degenerate);
+                }
+                self.restarts += 1;
+                self.start();
+            } else if let Some(reason) = reason{
+                break reason;
+            }
+            self.generation(criteria.max_evaluations.map(|m| (start_evaluations + m).saturating_sub(self.evaluations)));
+            self.iterations += 1;
+            if let Some(history) = &mut history{
+                history.push(IterationRecord{
+                    iteration: self.iterations,
+                    best_value: self.best.value,
+                    diameter: cast(self.state.as_ref().map_or(f64::INFINITY, |state| state.size()))
+                });
+            }
+        };
+        OptimizationResult{
+            best: self.best.clone(),
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: None,
+            restarts: self.restarts,
+            history: history,
+            stop_reason: stop_reason
+        }
+    }
+}
+
+impl<O: Objective<T>, T: Real> Optimizer for CmaEs<O, T>{
+    type Scalar = T;
+
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        CmaEs::run(self, criteria)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn sphere(x: &[f64]) -> f64{
+        x.iter().map(|xi| (xi - 1.0) * (xi - 1.0)).sum()
+    }
+
+    fn rastrigin(x: &[f64]) -> f64{
+        x.iter().map(|xi| xi * xi - 10.0 * (2.0 * std::f64::consts::PI * xi).cos() + 10.0).sum()
+    }
+
+    #[test]
+    fn minimizes_a_sphere(){
+        let mut cma_es = CmaEs::new(vec![5.0; 6].into_boxed_slice(), 2.0, None, sphere).unwrap();
+        let result = cma_es.run(&Termination::new().max_evaluations(20_000).f_tol(1e-14));
+        assert_eq!(result.stop_reason, StopReason::FunctionTolerance);
+        assert!(result.best.value < 1e-10, "{}", result.best.value);
+    }
+
+    #[test]
+    fn restarts_stay_within_the_budget(){
+        for max_evaluations in [1_237, 4_001, 9_999]{
+            let mut cma_es = CmaEs::new(vec![3.0; 4].into_boxed_slice(), 2.0, None, rastrigin).unwrap();
+            cma_es.max_restarts = 9;
+            let result = cma_es.run(&Termination::new().max_evaluations(max_evaluations).f_tol(1e-3));
+            assert_eq!(result.stop_reason, StopReason::MaxEvaluations);
+            // the guess counts as an evaluation before the run
+            assert_eq!(result.evaluations - 1, max_evaluations);
+            assert!(result.restarts > 0);
+        }
+
+        let mut cma_es = CmaEs::new(vec![3.0; 4].into_boxed_slice(), 2.0, None, rastrigin).unwrap();
+        cma_es.max_restarts = 9;
+        let result = cma_es.run(&Termination::new().max_iterations(300).f_tol(1e-3));
+        assert_eq!(result.stop_reason, StopReason::MaxIterations);
+        assert_eq!(result.iterations, 300);
+    }
+
+    #[test]
+    fn same_seed_same_run(){
+        let run = |seed: u64| {
+            let mut cma_es = CmaEs::new(vec![3.0; 4].into_boxed_slice(), 2.0, None, rastrigin).unwrap();
+            cma_es.seed = seed;
+            cma_es.run(&Termination::new().max_evaluations(2_000)).best
+        };
+        let (a, b) = (run(3), run(3));
+        assert_eq!(a.value.to_bits(), b.value.to_bits());
+        assert!(a.x.iter().zip(b.x.iter()).all(|(a, b)| a.to_bits() == b.to_bits()));
+    }
+
+    #[test]
+    fn keeps_to_the_bounds(){
+        let bounds = Bounds::new(vec![-1.0; 3], vec![0.5; 3]).unwrap();
+        let mut cma_es = CmaEs::new(vec![3.0; 3].into_boxed_slice(), 1.0, Some(&bounds), sphere).unwrap();
+        assert!(bounds.contains(&cma_es.best().x));
+        let result = cma_es.run(&Termination::new().max_evaluations(5_000).x_tol(1e-10));
+        assert!(result.best.x.iter().all(|x| (x - 0.5).abs() < 1e-6), "{:?}", result.best.x);
+    }
+
+    #[test]
+    fn rejects_invalid_sigma(){
+        assert_eq!(CmaEs::new(vec![0.0; 3].into_boxed_slice(), 0.0, None, sphere).err(), Some(Error::InvalidStep));
+        assert_eq!(CmaEs::new(vec![0.0; 3].into_boxed_slice(), f64::NAN, None, sphere).err(), Some(Error::InvalidStep));
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Serialize, Deserialize};
+
+// Reflection, expansion, contraction and shrink coefficients for NelderMeade.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Coefficients{
+    pub reflection: f64,
+    pub expansion: f64,
+    pub contraction: f64,
+    pub shrink: f64,
+}
+
+impl Coefficients{
+    // The classic 1 / 2 / 0.5 / 0.5 choice. Works well in low dimensions.
+    pub fn standard() -> Coefficients{
+        Coefficients{
+            reflection: 1.0,
+            expansion: 2.0,
+            contraction: 0.5,
+            shrink: 0.5
+        }
+    }
+
+    // Gao & Han (2012), "Implementing the Nelder-Mead simplex algorithm with adaptive parameters".
+    // Expansion and shrinking get gentler as the dimension grows, which keeps the simplex
+    // from degenerating on 20+ parameter problems. Equal to standard() for dimensions = 2.
+    pub fn adaptive(dimensions: usize) -> Coefficients{
+        let n = dimensions.max(1) as f64;
+        Coefficients{
+            reflection: 1.0,
+            expansion: 1.0 + 2.0 / n,
+            contraction: 0.75 - 1.0 / (2.0 * n),
+            shrink: 1.0 - 1.0 / n
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{nelder_meade::NelderMeade, error::Error, simplex::{Simplex, check_guess}, initial::InitialSimplex, point::Point, objective::{Objective, sanitize}, bounds::Bounds, termination::Termination};
+
+// Minimizes func(x) subject to g_i(x) <= 0 and h_j(x) = 0 by running NelderMeade on the
+// Powell-Hestenes-Rockafellar augmented Lagrangian and updating the multipliers between runs.
+pub struct AugmentedLagrangian<O: Objective>{
+    pub func: O,
+    pub inequalities: Vec<Box<dyn Objective>>,
+    pub equalities: Vec<Box<dyn Objective>>,
+    pub bounds: Option<Bounds>,
+    pub initial: InitialSimplex,
+    pub penalty: f64,
+    pub penalty_growth: f64,
+    pub max_penalty: f64,
+    pub outer_iterations: usize,
+    pub inner_termination: Termination,
+    pub tolerance: f64,
+}
+
+pub struct ConstrainedResult{
+    pub point: Point,
+    pub inequality_values: Box<[f64]>,
+    pub equality_values: Box<[f64]>,
+    // max(max_i max(0, g_i(x)), max_j |h_j(x)|)
+    pub violation: f64,
+    pub inequality_multipliers: Box<[f64]>,
+    pub equality_multipliers: Box<[f64]>,
+    pub outer_iterations: usize,
+}
+
+fn violation(inequality_values: &[f64], equality_values: &[f64]) -> f64{
+    let mut violation: f64 = 0.0;
+    for g in inequality_values{
+        violation = violation.max(*g);
+    }
+    for h in equality_values{
+        violation = violation.max(h.abs());
+    }
+    violation
+}
+
+impl<O: Objective> AugmentedLagrangian<O>{
+
+    pub fn new(func: O) -> AugmentedLagrangian<O>{
+        AugmentedLagrangian{
+            func: func,
+            inequalities: Vec::new(),
+            equalities: Vec::new(),
+            bounds: None,
+            initial: InitialSimplex::Shrinking(1.0),
+            penalty: 10.0,
+            penalty_growth: 10.0,
+            max_penalty: 1e8,
+            outer_iterations: 50,
+            inner_termination: Termination::default(),
+            tolerance: 1e-6
+        }
+    }
+
+    // g(x) <= 0
+    pub fn add_inequality(&mut self, g: impl Objective + 'static){
+        self.inequalities.push(Box::new(g));
+    }
+
+    // h(x) = 0
+    pub fn add_equality(&mut self, h: impl Objective + 'static){
+        self.equalities.push(Box::new(h));
+    }
+
+    fn constraint_values(&mut self, x: &[f64]) -> (Box<[f64]>, Box<[f64]>){
+        let inequality_values: Box<[f64]> = self.inequalities.iter_mut().map(|g| sanitize(g.evaluate(x))).collect();
+        let equality_values: Box<[f64]> = self.equalities.iter_mut().map(|h| sanitize(h.evaluate(x))).collect();
+        (inequality_values, equality_values)
+    }
+
+    fn minimize_lagrangian(&mut self, guess: Box<[f64]>, lambda: &[f64], nu: &[f64], rho: f64) -> Result<Box<[f64]>, Error>{
+        let func = &mut self.func;
+        let inequalities = &mut self.inequalities;
+        let equalities = &mut self.equalities;
+        let mut lagrangian = |x: &[f64]| -> f64 {
+            let mut value = func.evaluate(x);
+            for (i, g) in inequalities.iter_mut().enumerate(){
+                let shifted = (lambda[i] + rho * g.evaluate(x)).max(0.0);
+                value += (shifted * shifted - lambda[i] * lambda[i]) / (2.0 * rho);
+            }
+            for (j, h) in equalities.iter_mut().enumerate(){
+                let h_value = h.evaluate(x);
+                value += nu[j] * h_value + 0.5 * rho * h_value * h_value;
+            }
+            value
+        };
+
+        let simplex = Simplex::initial(guess, &self.initial, self.bounds.as_ref(), &mut lagrangian)?;
+        let mut nelder_meade = NelderMeade::adaptive(simplex, lagrangian);
+        nelder_meade.set_bounds(self.bounds.clone())?;
+        nelder_meade.run(&self.inner_termination);
+        let best = nelder_meade.needed_points()[0];
+        Ok(nelder_meade.simplex.points[best].x.clone())
+    }
+
+    pub fn minimize(&mut self, guess: Box<[f64]>) -> Result<ConstrainedResult, Error>{
+        let mut lambda: Box<[f64]> = vec![0.0; self.inequalities.len()].into_boxed_slice();
+        let mut nu: Box<[f64]> = vec![0.0; self.equalities.len()].into_boxed_slice();
+        let mut rho = self.penalty;
+
+        check_guess(&guess, self.bounds.as_ref())?;
+        let mut x = match &self.bounds{
+            Some(bounds) => bounds.project(guess)?,
+            None => guess
+        };
+        let mut value = sanitize(self.func.evaluate(&x));
+        let (mut inequality_values, mut equality_values) = self.constraint_values(&x);
+        let mut current_violation = violation(&inequality_values, &equality_values);
+        let mut outer_iterations = 0;
+
+        while outer_iterations < self.outer_iterations{
+            outer_iterations += 1;
+            x = self.minimize_lagrangian(x, &lambda, &nu, rho)?;
+
+            let previous_value = value;
+            let previous_violation = current_violation;
+            value = sanitize(self.func.evaluate(&x));
+            (inequality_values, equality_values) = self.constraint_values(&x);
+            current_violation = violation(&inequality_values, &equality_values);
+
+            for i in 0..lambda.len(){
+                lambda[i] = (lambda[i] + rho * inequality_values[i]).max(0.0);
+            }
+            for j in 0..nu.len(){
+                nu[j] += rho * equality_values[j];
+            }
+
+            if current_violation <= self.tolerance && (value - previous_value).abs() <= self.tolerance * (1.0 + value.abs()){
+                break;
+            }
+            // Not enough progress towards feasibility: weight the constraints more heavily.
+            if current_violation > 0.25 * previous_violation{
+                rho = (rho * self.penalty_growth).min(self.max_penalty);
+            }
+        }
+
+        Ok(ConstrainedResult{
+            point: Point{x: x, value: value},
+            inequality_values: inequality_values,
+            equality_values: equality_values,
+            violation: current_violation,
+            inequality_multipliers: lambda,
+            equality_multipliers: nu,
+            outer_iterations: outer_iterations
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn keeps_to_an_inequality(){
+        // smallest x0 + x1 on the unit disc
+        let mut problem = AugmentedLagrangian::new(|x: &[f64]| x[0] + x[1]);
+        problem.add_inequality(|x: &[f64]| x[0] * x[0] + x[1] * x[1] - 1.0);
+        let result = problem.minimize(vec![0.0, 0.0].into_boxed_slice()).unwrap();
+        let corner = -0.5_f64.sqrt();
+        assert!(result.violation < 1e-5, "{}", result.violation);
+        assert!((result.point.x[0] - corner).abs() < 1e-3 && (result.point.x[1] - corner).abs() < 1e-3, "{:?}", result.point.x);
+        assert!(result.inequality_multipliers[0] > 0.0);
+    }
+
+    #[test]
+    fn finds_the_multiplier_of_an_equality(){
+        // smallest x0^2 + x1^2 on the line x0 + x1 = 1: (0.5, 0.5) with multiplier -1
+        let mut problem = AugmentedLagrangian::new(|x: &[f64]| x[0] * x[0] + x[1] * x[1]);
+        problem.add_equality(|x: &[f64]| x[0] + x[1] - 1.0);
+        let result = problem.minimize(vec![3.0, -2.0].into_boxed_slice()).unwrap();
+        assert!(result.violation < 1e-5, "{}", result.violation);
+        assert!((result.point.x[0] - 0.5).abs() < 1e-3 && (result.point.x[1] - 0.5).abs() < 1e-3, "{:?}", result.point.x);
+        assert!((result.equality_multipliers[0] + 1.0).abs() < 1e-2, "{}", result.equality_multipliers[0]);
+    }
+
+    #[test]
+    fn rejects_bounds_of_another_dimension(){
+        let mut problem = AugmentedLagrangian::new(|x: &[f64]| x[0] + x[1]);
+        problem.bounds = Some(Bounds::new(vec![0.0; 3], vec![1.0; 3]).unwrap());
+        assert_eq!(problem.minimize(vec![0.5, 0.5].into_boxed_slice()).err(), Some(Error::DimensionMismatch{ expected: 2, found: 3 }));
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+// Everything the library reports through Result. Positions are 0-based indices into the
+// offending slice or list of points.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error{
+    // Two things that must have the same length do not (points, bounds, values for a batch).
+    DimensionMismatch{ expected: usize, found: usize },
+    // A simplex needs n + 1 points of dimension n, and n must be at least 2.
+    TooFewPoints{ found: usize },
+    // A starting guess needs at least 2 coordinates (for Subplex, 2 the bounds leave room in).
+    TooFewDimensions{ found: usize },
+    // The vertices are colinear, coplanar or coincide, so the simplex spans no volume.
+    DegenerateSimplex,
+    // A coordinate of a starting point or vertex is NaN or infinite.
+    NonFiniteStart{ position: usize },
+    // Every vertex of the initial simplex evaluated to NaN or infinity (or was rejected), so
+    // there is nothing to compare the search against.
+    NonFiniteStartValue,
+    // The initial step is NaN or infinite.
+    InvalidStep,
+    // The lower bound lies above the upper bound (or one of them is NaN).
+    InvalidBounds{ position: usize },
+    // A finite box is required (multi-start sampling) but this coordinate is unbounded.
+    UnboundedCoordinate{ position: usize },
+    IndexOutOfRange{ index: usize, length: usize },
+    // Subplex subspaces need at least 2 coordinates and min must not exceed max.
+    InvalidSubspaceSizes{ min: usize, max: usize },
+    // A noise policy needs 1 <= samples <= max_samples.
+    InvalidSampleCounts{ samples: usize, max_samples: usize },
+    // A benchmark suite was asked to run no trials.
+    NoTrials,
+    // An expression (see Expression::parse) that does not parse. position counts characters.
+    Syntax{ position: usize, message: String },
+    // Command line arguments that make no sense, see cli::Options::parse.
+    Usage(String),
+    // Reading or writing a checkpoint file failed.
+    Io(String),
+    // A checkpoint could not be encoded or decoded.
+    Serialization(String),
+}
+
+impl fmt::Display for Error{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            Error::DimensionMismatch{ expected, found } => write!(f, "Length mismatch. Expected length {}, found {}", expected, found),
+            Error::TooFewPoints{ found } => write!(f, "Too few points ({}). A simplex needs n+1 points of exactly dimension n. n must be at least 2", found),
+            Error::TooFewDimensions{ found } => write!(f, "Too few dimensions ({}). The guess needs at least 2 coordinates", found),
+            Error::DegenerateSimplex => write!(f, "Degenerate simplex. The points are colinear, coplanar or coincide"),
+            Error::NonFiniteStart{ position } => write!(f, "Coordinate {} of the starting point is not finite", position),
+            Error::NonFiniteStartValue => write!(f, "The objective is not finite at any vertex of the initial simplex"),
+            Error::InvalidStep => write!(f, "The initial step is not finite"),
+            Error::InvalidBounds{ position } => write!(f, "Invalid bounds in position {}. Lower bound is greater than upper bound", position),
+            Error::UnboundedCoordinate{ position } => write!(f, "Bounds in position {} are not finite. Multi-start needs a finite box to sample from", position),
+            Error::IndexOutOfRange{ index, length } => write!(f, "Index out of range. Slice (length: {}) does not contain index ({})", length, index),
+            Error::InvalidSubspaceSizes{ min, max } => write!(f, "Invalid subspace sizes {}..{}. Subspaces need at least 2 coordinates and the minimum must not exceed the maximum", min, max),
+            Error::InvalidSampleCounts{ samples, max_samples } => write!(f, "Invalid sample counts. Samples ({}) must be at least 1 and at most max_samples ({})", samples, max_samples),
+            Error::NoTrials => write!(f, "A benchmark needs at least one trial"),
+            Error::Syntax{ position, message } => write!(f, "Syntax error at position {}: {}", position, message),
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::Io(message) => write!(f, "{}", message),
+            Error::Serialization(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+impl std::error::Error for Error{}
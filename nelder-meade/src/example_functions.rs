@@ -7,10 +7,72 @@ pub fn exponential(coords : &[f64]) -> f64{
     std::f64::consts::E.powf(exponent)
 }
 
-pub fn dot_product(coords: &[f64]) -> f64{
+// Sum of squares. Minimum 0 at the origin.
+pub fn sphere(coords: &[f64]) -> f64{
     let mut total : f64 = 0.0;
     for x in coords{
         total += x * x;
     }
     total
 }
+
+// Minimum 0 at (1, ..., 1).
+pub fn rosenbrock(coords: &[f64]) -> f64{
+    let mut total : f64 = 0.0;
+    for i in 0..coords.len() - 1{
+        total += 100.0 * (coords[i + 1] - coords[i] * coords[i]).powi(2) + (1.0 - coords[i]).powi(2);
+    }
+    total
+}
+
+// Four minima with value 0, one of them at (3, 2).
+pub fn himmelblau(coords: &[f64]) -> f64{
+    let (x, y) = (coords[0], coords[1]);
+    (x * x + y - 11.0).powi(2) + (x + y * y - 7.0).powi(2)
+}
+
+// Minimum 0 at (3, 0.5).
+pub fn beale(coords: &[f64]) -> f64{
+    let (x, y) = (coords[0], coords[1]);
+    (1.5 - x + x * y).powi(2) + (2.25 - x + x * y * y).powi(2) + (2.625 - x + x * y * y * y).powi(2)
+}
+
+// Minimum 0 at (1, 3).
+pub fn booth(coords: &[f64]) -> f64{
+    let (x, y) = (coords[0], coords[1]);
+    (x + 2.0 * y - 7.0).powi(2) + (2.0 * x + y - 5.0).powi(2)
+}
+
+// Highly multimodal. Minimum 0 at the origin.
+pub fn rastrigin(coords: &[f64]) -> f64{
+    let mut total : f64 = 10.0 * coords.len() as f64;
+    for x in coords{
+        total += x * x - 10.0 * (2.0 * std::f64::consts::PI * x).cos();
+    }
+    total
+}
+
+// Nearly flat outer region with many shallow local minima. Minimum 0 at the origin.
+pub fn ackley(coords: &[f64]) -> f64{
+    let n = coords.len() as f64;
+    let mut squares : f64 = 0.0;
+    let mut cosines : f64 = 0.0;
+    for x in coords{
+        squares += x * x;
+        cosines += (2.0 * std::f64::consts::PI * x).cos();
+    }
+    -20.0 * (-0.2 * (squares / n).sqrt()).exp() - (cosines / n).exp() + 20.0 + std::f64::consts::E
+}
+
+// Four dimensions, singular Hessian at the minimum 0 at the origin.
+pub fn powell_singular(coords: &[f64]) -> f64{
+    let (a, b, c, d) = (coords[0], coords[1], coords[2], coords[3]);
+    (a + 10.0 * b).powi(2) + 5.0 * (c - d).powi(2) + (b - 2.0 * c).powi(4) + 10.0 * (a - d).powi(4)
+}
+
+// Four dimensions. Minimum 0 at (1, 1, 1, 1).
+pub fn wood(coords: &[f64]) -> f64{
+    let (a, b, c, d) = (coords[0], coords[1], coords[2], coords[3]);
+    100.0 * (a * a - b).powi(2) + (a - 1.0).powi(2) + (c - 1.0).powi(2) + 90.0 * (c * c - d).powi(2)
+        + 10.1 * ((b - 1.0).powi(2) + (d - 1.0).powi(2)) + 19.8 * (b - 1.0) * (d - 1.0)
+}
@@ -0,0 +1,467 @@
+use std::{fmt, mem};
+
+use crate::error::Error;
+
+// Parses and evaluates objectives written as text, e.g. "(1-x0)^2 + 100*(x1-x0^2)^2".
+//
+// expression := term (('+' | '-') term)*
+// term       := unary (('*' | '/') unary)*
+// unary      := ('-' | '+') unary | power
+// power      := atom ('^' unary)?
+// atom       := number | x<index> | pi | e | function '(' expression ')' | '(' expression ')'
+//
+// so -x0^2 is -(x0^2) and 2^3^2 is 2^(3^2).
+
+// Variables go up to x<MAX_VARIABLES - 1>, so dimensions() cannot overflow and the start point
+// stays a reasonable size.
+pub const MAX_VARIABLES: usize = 100_000;
+
+// Parentheses, function calls, signs and exponents nest at most MAX_DEPTH deep, so parsing
+// cannot overflow the stack. Long sums and products are not limited; evaluate(), dimensions()
+// and dropping walk the tree without recursion.
+pub const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function{
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Ln,
+    Log10,
+    Sqrt,
+    Abs,
+}
+
+impl Function{
+    pub fn name(&self) -> &'static str{
+        match self{
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Tan => "tan",
+            Function::Exp => "exp",
+            Function::Ln => "ln",
+            Function::Log10 => "log",
+            Function::Sqrt => "sqrt",
+            Function::Abs => "abs"
+        }
+    }
+
+    fn apply(&self, a: f64) -> f64{
+        match self{
+            Function::Sin => a.sin(),
+            Function::Cos => a.cos(),
+            Function::Tan => a.tan(),
+            Function::Exp => a.exp(),
+            Function::Ln => a.ln(),
+            Function::Log10 => a.log10(),
+            Function::Sqrt => a.sqrt(),
+            Function::Abs => a.abs()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator{
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+impl Operator{
+    fn apply(&self, a: f64, b: f64) -> f64{
+        match self{
+            Operator::Add => a + b,
+            Operator::Subtract => a - b,
+            Operator::Multiply => a * b,
+            Operator::Divide => a / b,
+            Operator::Power => if b.fract() == 0.0 && b.abs() <= i32::MAX as f64 { a.powi(b as i32) } else { a.powf(b) }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression{
+    Number(f64),
+    Variable(usize),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Box<Expression>),
+}
+
+// Work left for evaluate(): the value of an expression, or the operation of an expression whose
+// operands are already on the value stack.
+enum Task<'a>{
+    Visit(&'a Expression),
+    Apply(&'a Expression),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token{
+    Number(f64),
+    Identifier(String),
+    Operator(char),
+    OpenParen,
+    CloseParen,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, Error>{
+    let characters: Vec<char> = text.chars().collect();
+    let mut tokens: Vec<(usize, Token)> = Vec::new();
+    let mut i = 0;
+    while i < characters.len(){
+        let c = characters[i];
+        if c.is_whitespace(){
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.'{
+            let start = i;
+            while i < characters.len() && (characters[i].is_ascii_digit() || characters[i] == '.'){
+                i += 1;
+            }
+            // exponent, as in 1e-3 or 2.5E4
+            if i < characters.len() && (characters[i] == 'e' || characters[i] == 'E'){
+                let mut j = i + 1;
+                if j < characters.len() && (characters[j] == '+' || characters[j] == '-'){
+                    j += 1;
+                }
+                if j < characters.len() && characters[j].is_ascii_digit(){
+                    i = j;
+                    while i < characters.len() && characters[i].is_ascii_digit(){
+                        i += 1;
+                    }
+                }
+            }
+            let literal: String = characters[start..i].iter().collect();
+            match literal.parse::<f64>(){
+                Ok(number) => tokens.push((start, Token::Number(number))),
+                Err(_) => return Err(Error::Syntax{ position: start, message: format!("Invalid number '{}'", literal) })
+            }
+        } else if c.is_alphabetic() || c == '_'{
+            let start = i;
+            while i < characters.len() && (characters[i].is_alphanumeric() || characters[i] == '_'){
+                i += 1;
+            }
+            tokens.push((start, Token::Identifier(characters[start..i].iter().collect())));
+        } else if "+-*/^".contains(c){
+            tokens.push((i, Token::Operator(c)));
+            i += 1;
+        } else if c == '('{
+            tokens.push((i, Token::OpenParen));
+            i += 1;
+        } else if c == ')'{
+            tokens.push((i, Token::CloseParen));
+            i += 1;
+        } else {
+            return Err(Error::Syntax{ position: i, message: format!("Unexpected character '{}'", c) });
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser{
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    length: usize,
+    // Calls of unary() in progress, see MAX_DEPTH.
+    depth: usize,
+}
+
+impl Parser{
+    fn peek(&self) -> Option<&Token>{
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn location(&self) -> usize{
+        self.tokens.get(self.position).map(|(location, _)| *location).unwrap_or(self.length)
+    }
+
+    fn next(&mut self) -> Option<Token>{
+        let token = self.tokens.get(self.position).map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn expression(&mut self) -> Result<Expression, Error>{
+        let mut left = self.term()?;
+        while let Some(Token::Operator(c)) = self.peek(){
+            let operator = match c{
+                '+' => Operator::Add,
+                '-' => Operator::Subtract,
+                _ => break
+            };
+            self.next();
+            let right = self.term()?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expression, Error>{
+        let mut left = self.unary()?;
+        while let Some(Token::Operator(c)) = self.peek(){
+            let operator = match c{
+                '*' => Operator::Multiply,
+                '/' => Operator::Divide,
+                _ => break
+            };
+            self.next();
+            let right = self.unary()?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // Every recursion of the parser goes through here.
+    fn unary(&mut self) -> Result<Expression, Error>{
+        if self.depth == MAX_DEPTH{
+            return Err(Error::Syntax{ position: self.location(), message: format!("Expression nested more than {} levels deep", MAX_DEPTH) });
+        }
+        self.depth += 1;
+        let unary = self.signed();
+        self.depth -= 1;
+        unary
+    }
+
+    fn signed(&mut self) -> Result<Expression, Error>{
+        match self.peek(){
+            Some(Token::Operator('-')) => {
+                self.next();
+                Ok(Expression::Negate(Box::new(self.unary()?)))
+            },
+            Some(Token::Operator('+')) => {
+                self.next();
+                self.unary()
+            },
+            _ => self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expression, Error>{
+        let base = self.atom()?;
+        if let Some(Token::Operator('^')) = self.peek(){
+            self.next();
+            let exponent = self.unary()?;
+            return Ok(Expression::Binary(Operator::Power, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expression, Error>{
+        let location = self.location();
+        match self.next(){
+            Some(Token::Number(number)) => Ok(Expression::Number(number)),
+            Some(Token::OpenParen) => {
+                let inner = self.expression()?;
+                let close_location = self.location();
+                match self.next(){
+                    Some(Token::CloseParen) => Ok(inner),
+                    _ => Err(Error::Syntax{ position: close_location, message: "Expected ')'".to_owned() })
+                }
+            },
+            Some(Token::Identifier(name)) => {
+                if let Some(index) = name.strip_prefix('x'){
+                    if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()){
+                        return match index.parse::<usize>(){
+                            Ok(index) if index < MAX_VARIABLES => Ok(Expression::Variable(index)),
+                            _ => Err(Error::Syntax{ position: location, message: format!("Variable '{}' is out of range, the largest is x{}", name, MAX_VARIABLES - 1) })
+                        };
+                    }
+                }
+                let function = match name.as_str(){
+                    "pi" => return Ok(Expression::Number(std::f64::consts::PI)),
+                    "e" => return Ok(Expression::Number(std::f64::consts::E)),
+                    "sin" => Function::Sin,
+                    "cos" => Function::Cos,
+                    "tan" => Function::Tan,
+                    "exp" => Function::Exp,
+                    "ln" => Function::Ln,
+                    "log" => Function::Log10,
+                    "sqrt" => Function::Sqrt,
+                    "abs" => Function::Abs,
+                    _ => return Err(Error::Syntax{ position: location, message: format!("Unknown name '{}'. Variables are written x0, x1, ...", name) })
+                };
+                match self.next(){
+                    Some(Token::OpenParen) => {},
+                    _ => return Err(Error::Syntax{ position: location, message: format!("Expected '(' after '{}'", name) })
+                }
+                let argument = self.expression()?;
+                let close_location = self.location();
+                match self.next(){
+                    Some(Token::CloseParen) => Ok(Expression::Call(function, Box::new(argument))),
+                    _ => Err(Error::Syntax{ position: close_location, message: format!("Expected ')' to close '{}'", name) })
+                }
+            },
+            Some(token) => Err(Error::Syntax{ position: location, message: format!("Unexpected {:?}", token) }),
+            None => Err(Error::Syntax{ position: location, message: "Unexpected end of expression".to_owned() })
+        }
+    }
+}
+
+impl Expression{
+    pub fn parse(text: &str) -> Result<Expression, Error>{
+        let mut parser = Parser{
+            tokens: tokenize(text)?,
+            position: 0,
+            length: text.chars().count(),
+            depth: 0
+        };
+        let expression = parser.expression()?;
+        if parser.position < parser.tokens.len(){
+            return Err(Error::Syntax{ position: parser.location(), message: "Unexpected input".to_owned() });
+        }
+        Ok(expression)
+    }
+
+    // Number of coordinates the expression needs: one more than the largest variable index.
+    pub fn dimensions(&self) -> usize{
+        let mut dimensions = 0;
+        let mut stack = vec![self];
+        while let Some(expression) = stack.pop(){
+            match expression{
+                Expression::Number(_) => {},
+                Expression::Variable(index) => dimensions = dimensions.max(index + 1),
+                Expression::Negate(inner) | Expression::Call(_, inner) => stack.push(inner),
+                Expression::Binary(_, left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        dimensions
+    }
+
+    // Variables missing from x evaluate to NaN.
+    pub fn evaluate(&self, x: &[f64]) -> f64{
+        let mut tasks = vec![Task::Visit(self)];
+        let mut values: Vec<f64> = Vec::new();
+        while let Some(task) = tasks.pop(){
+            match task{
+                Task::Visit(expression) => match expression{
+                    Expression::Number(number) => values.push(*number),
+                    Expression::Variable(index) => values.push(x.get(*index).copied().unwrap_or(f64::NAN)),
+                    Expression::Negate(inner) | Expression::Call(_, inner) => {
+                        tasks.push(Task::Apply(expression));
+                        tasks.push(Task::Visit(inner));
+                    },
+                    Expression::Binary(_, left, right) => {
+                        tasks.push(Task::Apply(expression));
+                        tasks.push(Task::Visit(right));
+                        tasks.push(Task::Visit(left));
+                    }
+                },
+                Task::Apply(expression) => {
+                    // the operands were pushed by the visits above, left before right
+                    let b = values.pop().unwrap_or(f64::NAN);
+                    let value = match expression{
+                        Expression::Negate(_) => -b,
+                        Expression::Call(function, _) => function.apply(b),
+                        Expression::Binary(operator, _, _) => {
+                            let a = values.pop().unwrap_or(f64::NAN);
+                            operator.apply(a, b)
+                        },
+                        Expression::Number(_) | Expression::Variable(_) => b
+                    };
+                    values.push(value);
+                }
+            }
+        }
+        values.pop().unwrap_or(f64::NAN)
+    }
+}
+
+// Moves the children out before they are dropped, so a long chain of operations does not drop
+// recursively.
+impl Drop for Expression{
+    fn drop(&mut self){
+        let mut stack: Vec<Expression> = Vec::new();
+        take_children(self, &mut stack);
+        while let Some(mut expression) = stack.pop(){
+            take_children(&mut expression, &mut stack);
+        }
+    }
+}
+
+fn take_children(expression: &mut Expression, stack: &mut Vec<Expression>){
+    match expression{
+        Expression::Number(_) | Expression::Variable(_) => {},
+        Expression::Negate(inner) | Expression::Call(_, inner) => stack.push(mem::replace(&mut **inner, Expression::Number(0.0))),
+        Expression::Binary(_, left, right) => {
+            stack.push(mem::replace(&mut **left, Expression::Number(0.0)));
+            stack.push(mem::replace(&mut **right, Expression::Number(0.0)));
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            Expression::Number(number) => write!(f, "{}", number),
+            Expression::Variable(index) => write!(f, "x{}", index),
+            Expression::Negate(inner) => write!(f, "-({})", inner),
+            Expression::Binary(operator, left, right) => {
+                let symbol = match operator{
+                    Operator::Add => "+",
+                    Operator::Subtract => "-",
+                    Operator::Multiply => "*",
+                    Operator::Divide => "/",
+                    Operator::Power => "^"
+                };
+                write!(f, "({} {} {})", left, symbol, right)
+            },
+            Expression::Call(function, inner) => write!(f, "{}({})", function.name(), inner)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn parses_with_precedence(){
+        let expression = Expression::parse("-x0^2 + 2^3^2 * x1").unwrap();
+        assert_eq!(expression.dimensions(), 2);
+        assert_eq!(expression.evaluate(&[3.0, 1.0]), -9.0 + 512.0);
+    }
+
+    #[test]
+    fn rejects_out_of_range_variables(){
+        assert!(Expression::parse("x18446744073709551615").is_err());
+        assert!(Expression::parse("x99999999999999999999999").is_err());
+        assert!(Expression::parse(&format!("x{}", MAX_VARIABLES)).is_err());
+        assert_eq!(Expression::parse(&format!("x{}", MAX_VARIABLES - 1)).unwrap().dimensions(), MAX_VARIABLES);
+    }
+
+    #[test]
+    fn reports_syntax_errors(){
+        let position = |text: &str| match Expression::parse(text){
+            Err(Error::Syntax{ position, .. }) => Some(position),
+            _ => None
+        };
+        assert_eq!(position("(x0 + 1"), Some(7));
+        assert_eq!(position("sin(x0"), Some(6));
+        assert_eq!(position("sin x0"), Some(0));
+        assert_eq!(position("x0 $ 1"), Some(3));
+        assert_eq!(position("foo(x0)"), Some(0));
+        assert_eq!(position("1.2.3"), Some(0));
+        assert_eq!(position("x0 x1"), Some(3));
+    }
+
+    #[test]
+    fn limits_the_nesting_depth(){
+        let nested = |depth: usize| format!("{}x0{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(Expression::parse(&nested(MAX_DEPTH - 1)).unwrap().evaluate(&[2.0]), 2.0);
+        assert!(matches!(Expression::parse(&nested(MAX_DEPTH)), Err(Error::Syntax{ position, .. }) if position == MAX_DEPTH));
+        assert!(matches!(Expression::parse(&"-".repeat(100_000)), Err(Error::Syntax{ .. })));
+        assert!(matches!(Expression::parse(&"x0^".repeat(100_000)), Err(Error::Syntax{ .. })));
+    }
+
+    #[test]
+    fn long_sums_do_not_recurse(){
+        let sum = Expression::parse(&vec!["x1"; 200_000].join(" + ")).unwrap();
+        assert_eq!(sum.dimensions(), 2);
+        assert_eq!(sum.evaluate(&[0.0, 0.5]), 100_000.0);
+    }
+}
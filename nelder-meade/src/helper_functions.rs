@@ -1,52 +1,43 @@
-pub fn add_to_slice(mut return_slice: Box<[f64]>, slice_to_add: &[f64]) -> Result<Box<[f64]>, String> {
-    if return_slice.len() != slice_to_add.len(){
-        return Err(format!("Length mismatch. Length of return slice ({}) does not equal length of slice to add ({})", return_slice.len(), slice_to_add.len()));
-    }
-    for index in  0..slice_to_add.len(){
-        return_slice[index] += slice_to_add[index];
+use crate::{real::{Real, cast}, error::Error};
+
+pub fn add_to_index<T: Real>(mut return_slice: Box<[T]>, index: usize, step: T) -> Result<Box<[T]>, Error> {
+    if return_slice.len() <= index{
+        return Err(Error::IndexOutOfRange{ index: index, length: return_slice.len() });
     }
+    return_slice[index] += step;
     Ok(return_slice)
 }
 
-pub fn sub_to_slice(mut return_slice: Box<[f64]>, slice_to_add: &[f64]) -> Result<Box<[f64]>, String>{
-    if return_slice.len() != slice_to_add.len(){
-        return Err(format!("Length mismatch. Length of return slice ({}) does not equal length of slice to add ({})", return_slice.len(), slice_to_add.len()));
+pub fn distance<T: Real>(p1: &[T], p2: &[T]) -> Result<T, Error>{
+    if p1.len() != p2.len(){
+        return Err(Error::DimensionMismatch{ expected: p1.len(), found: p2.len() });
     }
-    for index in  0..slice_to_add.len(){
-        return_slice[index] -= slice_to_add[index];
+    let mut distance = T::zero();
+    for i in 0..p1.len(){
+        distance += (p1[i] - p2[i]).powi(2);
     }
-    Ok(return_slice)
+    Ok(distance.powf(cast(0.5)))
 }
 
-pub fn multiply_by_const(mut return_slice: Box<[f64]>, constant:f64) -> Box<[f64]>{
-    for index in 0..return_slice.len(){
-        return_slice[index] *= constant;
-    }
-    return_slice
-}
+// In-place kernels for the inner loop. They write into existing buffers instead of returning
+// new ones; the lengths are the caller's responsibility.
 
-pub fn add_to_index(mut return_slice: Box<[f64]>, index: usize, step: f64) -> Result<Box<[f64]>, String> {
-    if return_slice.len() <= index{
-        return Err(format!("Index out of range. Slice (length: {}) does not contain index ({})", return_slice.len(), index));
+// out = origin + t * (target - origin)
+pub fn affine_combination<T: Real>(out: &mut [T], origin: &[T], target: &[T], t: T){
+    for index in 0..out.len(){
+        out[index] = origin[index] + t * (target[index] - origin[index]);
     }
-    return_slice[index] += step;
-    return Ok(return_slice); 
 }
 
-pub fn distance(p1: &[f64], p2: &[f64]) -> Result<f64, String>{
-    if p1.len() != p2.len(){
-        return Err(format!("Length mismatch. P1 length:{} P2 length: {}", p1.len(), p2.len()));
+// point = anchor + t * (point - anchor)
+pub fn move_towards<T: Real>(point: &mut [T], anchor: &[T], t: T){
+    for index in 0..point.len(){
+        point[index] = anchor[index] + t * (point[index] - anchor[index]);
     }
-    let mut distance = 0.0;
-    for i in 0..p1.len(){
-        distance += (p1[i] - p2[i]).powi(2);
-    }
-    Ok(distance.powf(0.5))
 }
 
-pub fn absolute_value(a:f64) -> f64{
-    if a > 0.0{
-        return a
+pub fn add_assign<T: Real>(sum: &mut [T], slice_to_add: &[T]){
+    for index in 0..sum.len(){
+        sum[index] += slice_to_add[index];
     }
-    a * -1.0
-}
\ No newline at end of file
+}
@@ -0,0 +1,192 @@
+use std::time::Instant;
+
+use crate::{point::Point, objective::{Objective, sanitize}, real::{Real, cast}, bounds::Bounds, error::Error, optimizer::{Optimizer, feasible_guess}, termination::{Termination, StopReason}, result::{OptimizationResult, IterationRecord}};
+
+// Hooke & Jeeves (1961) pattern search. Explores the coordinate directions around a base point
+// one at a time and, after a successful exploration, jumps ahead along the direction of the
+// improvement before exploring again. The step is reduced when no coordinate move helps.
+pub struct HookeJeeves<O, T = f64> where O: Objective<T>{
+    pub func: O,
+    // Length of the coordinate moves. Shrinks as the search closes in.
+    pub step: T,
+    // Factor the step is multiplied by after an exploration without improvement.
+    pub step_reduction: f64,
+    pub iterations: usize,
+    pub evaluations: usize,
+    // Keep a per-iteration record of the best value and step size in run().
+    pub record_history: bool,
+    // The guess was projected onto them, see new.
+    bounds: Option<Bounds>,
+    base: Point<T>,
+    // The base point before the last successful iteration, to extrapolate the pattern from.
+    previous: Option<Box<[T]>>,
+    // Largest minus smallest value seen in the last exploration, for Termination::f_tol.
+    spread: T,
+}
+
+impl<O: Objective<T>, T: Real> HookeJeeves<O, T>{
+
+    // Starts from the guess projected onto the bounds, if any.
+    pub fn new(guess: Box<[T]>, step: T, bounds: Option<&Bounds>, mut func: O) -> Result<HookeJeeves<O, T>, Error>{
+        let guess = feasible_guess(guess, bounds, &func)?;
+        if !step.is_finite() || step <= T::zero(){
+            return Err(Error::InvalidStep);
+        }
+        let value = sanitize(func.evaluate(&guess));
+        Ok(HookeJeeves{
+            func: func,
+            bounds: bounds.cloned(),
+            step: step,
+            step_reduction: 0.5,
+            iterations: 0,
+            evaluations: 1,
+            record_history: false,
+            base: Point{x: guess, value: value},
+            previous: None,
+            spread: T::infinity()
+        })
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.bounds.as_ref()
+    }
+
+    pub fn best(&self) -> &Point<T>{
+        &self.base
+    }
+
+    fn evaluate(&mut self, x: &[T]) -> T{
+        self.evaluations += 1;
+        sanitize(self.func.evaluate(x))
+    }
+
+    // Tries +step and -step along every coordinate in turn, keeping every move that improves.
+    fn explore(&mut self, mut center: Point<T>) -> Point<T>{
+        let mut smallest = center.value;
+        let mut largest = center.value;
+        for i in 0..center.x.len(){
+            let original = center.x[i];
+            for direction in [T::one(), -T::one()]{
+                center.x[i] = original + direction * self.step;
+                if let Some(bounds) = &self.bounds{
+                    bounds.clamp(&mut center.x);
+                }
+                if center.x[i] == original{
+                    continue;
+                }
+                let value = self.evaluate(&center.x);
+                smallest = smallest.min(value);
+                largest = largest.max(value);
+                if value < center.value{
+                    center.value = value;
+                    break;
+                }
+                center.x[i] = original;
+            }
+        }
+        self.spread = largest - smallest;
+        center
+    }
+
+    // One pattern move and exploration after a successful iteration, one exploration around
+    // the base point otherwise.
+    pub fn step(&mut self){
+        if let Some(previous) = self.previous.take(){
+            let mut pattern: Box<[T]> = self.base.x.clone();
+            for i in 0..pattern.len(){
+                pattern[i] += pattern[i] - previous[i];
+            }
+            if let Some(bounds) = &self.bounds{
+                bounds.clamp(&mut pattern);
+            }
+            let value = self.evaluate(&pattern);
+            let explored = self.explore(Point{x: pattern, value: value});
+            if explored.value < self.base.value{
+                self.previous = Some(std::mem::replace(&mut self.base, explored).x);
+            }
+            // otherwise the next iteration explores around the base point again
+            return;
+        }
+        let explored = self.explore(self.base.clone());
+        if explored.value < self.base.value{
+            self.previous = Some(std::mem::replace(&mut self.base, explored).x);
+        } else {
+            self.step *= cast(self.step_reduction);
+        }
+    }
+
+    pub fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+        let start_evaluations = self.evaluations;
+        let mut history: Option<Vec<IterationRecord<T>>> = if self.record_history { Some(Vec::new()) } else { None };
+        let stop_reason: StopReason = loop {
+            if let Some(reason) = criteria.check_progress(self.spread, self.step, self.iterations - start_iterations, self.evaluations - start_evaluations, start){
+                break reason;
+            }
+            self.step();
+            self.iterations += 1;
+            if let Some(history) = &mut history{
+                history.push(IterationRecord{
+                    iteration: self.iterations,
+                    best_value: self.base.value,
+                    diameter: self.step
+                });
+            }
+        };
+        OptimizationResult{
+            best: self.base.clone(),
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: None,
+            restarts: 0,
+            history: history,
+            stop_reason: stop_reason
+        }
+    }
+}
+
+impl<O: Objective<T>, T: Real> Optimizer for HookeJeeves<O, T>{
+    type Scalar = T;
+
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        HookeJeeves::run(self, criteria)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn rosenbrock(x: &[f64]) -> f64{
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)
+    }
+
+    #[test]
+    fn minimizes_rosenbrock(){
+        let mut hooke_jeeves = HookeJeeves::new(vec![-1.2, 1.0].into_boxed_slice(), 0.5, None, rosenbrock).unwrap();
+        hooke_jeeves.record_history = true;
+        let result = hooke_jeeves.run(&Termination::new().max_iterations(100_000).x_tol(1e-10));
+        assert_eq!(result.stop_reason, StopReason::StepTolerance);
+        assert!((result.best.x[0] - 1.0).abs() < 1e-4 && (result.best.x[1] - 1.0).abs() < 1e-4, "{:?}", result.best.x);
+        let history = result.history.unwrap();
+        assert_eq!(history.len(), result.iterations);
+        assert!(history.windows(2).all(|w| w[1].best_value <= w[0].best_value));
+    }
+
+    #[test]
+    fn reduces_the_step_at_a_minimum(){
+        let mut hooke_jeeves = HookeJeeves::new(vec![0.0, 0.0].into_boxed_slice(), 1.0, None, |x: &[f64]| x[0] * x[0] + x[1] * x[1]).unwrap();
+        hooke_jeeves.step();
+        assert_eq!(hooke_jeeves.step, 0.5);
+        assert_eq!(hooke_jeeves.evaluations, 1 + 4);
+        assert_eq!(hooke_jeeves.best().x[..], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_invalid_steps(){
+        for step in [0.0, -1.0, f64::INFINITY]{
+            assert_eq!(HookeJeeves::new(vec![0.0, 0.0].into_boxed_slice(), step, None, rosenbrock).err(), Some(Error::InvalidStep));
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{simplex::{Simplex, check_guess, check_vertices}, bounds::Bounds, real::{Real, cast}, error::Error};
+
+// How Simplex::initial, FixedSimplex::initial and AskTell::initial place the n + 1 starting
+// vertices around a guess. The guess itself is always one of the vertices, except for Shrinking.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum InitialSimplex{
+    // The construction of Simplex::from_guess: shrinking steps along every axis with the
+    // guess moved towards the opposite side, and the sign of the last coordinate flipped.
+    Shrinking(f64),
+    // guess + step * e_i: a right-angled simplex with edges of length step along the axes.
+    Axis(f64),
+    // Every edge has this length (Spendley, Hext & Himsworth, 1962).
+    Regular(f64),
+    // guess + relative * guess_i * e_i, or guess + zero * e_i where guess_i is 0. Adapts to the
+    // scale of every coordinate without a step. See InitialSimplex::pfeffer.
+    Pfeffer{ relative: f64, zero: f64 },
+    // guess + steps[i] * e_i, for coordinates whose scales differ by orders of magnitude.
+    Steps(Box<[f64]>),
+}
+
+impl InitialSimplex{
+    // 5% of every non-zero coordinate and 0.00025 for zeros, as in MATLAB's fminsearch.
+    pub fn pfeffer() -> InitialSimplex{
+        InitialSimplex::Pfeffer{ relative: 0.05, zero: 0.00025 }
+    }
+
+    // The vertices, without evaluating them. With bounds, the guess is projected onto the box,
+    // every coordinate in which a vertex would leave the box is stepped in the other direction
+    // (which keeps the shape of the simplex), and vertices that still do not fit are projected.
+    pub fn vertices<T: Real>(&self, point: Box<[T]>, bounds: Option<&Bounds>) -> Result<Vec<Box<[T]>>, Error>{
+        if let InitialSimplex::Shrinking(step) = self{
+            return Simplex::guess_vertices(point, cast(*step), bounds);
+        }
+        check_guess(&point, bounds)?;
+        let point = match bounds{
+            Some(bounds) => bounds.project(point)?,
+            None => point
+        };
+        let dimensions = point.len();
+        let mut offsets: Vec<Box<[T]>> = match self{
+            InitialSimplex::Shrinking(_) => unreachable!(),
+            InitialSimplex::Axis(step) => axis_offsets(&vec![*step; dimensions])?,
+            InitialSimplex::Regular(edge) => {
+                if !edge.is_finite(){
+                    return Err(Error::InvalidStep);
+                }
+                // vertex i = guess + q * (1, ..., 1) + (p - q) * e_i
+                let n = dimensions as f64;
+                let p = edge / (n * 2.0_f64.sqrt()) * ((n + 1.0).sqrt() + n - 1.0);
+                let q = edge / (n * 2.0_f64.sqrt()) * ((n + 1.0).sqrt() - 1.0);
+                (0..dimensions).map(|i| (0..dimensions).map(|k| cast(if k == i { p } else { q })).collect()).collect()
+            },
+            InitialSimplex::Pfeffer{ relative, zero } => {
+                let steps: Vec<f64> = point.iter()
+                    .map(|x| if *x == T::zero() { *zero } else { relative * x.to_f64().unwrap() })
+                    .collect();
+                axis_offsets(&steps)?
+            },
+            InitialSimplex::Steps(steps) => {
+                if steps.len() != dimensions{
+                    return Err(Error::DimensionMismatch{ expected: dimensions, found: steps.len() });
+                }
+                axis_offsets(steps)?
+            }
+        };
+
+        if let Some(bounds) = bounds{
+            for k in 0..dimensions{
+                let outside = offsets.iter().any(|offset| !bounds_contain(bounds, k, point[k] + offset[k]));
+                if outside{
+                    for offset in offsets.iter_mut(){
+                        offset[k] = -offset[k];
+                    }
+                }
+            }
+        }
+
+        let mut vertices: Vec<Box<[T]>> = Vec::with_capacity(dimensions + 1);
+        vertices.push(point.clone());
+        for offset in offsets{
+            let mut vertex = point.clone();
+            for k in 0..dimensions{
+                vertex[k] += offset[k];
+            }
+            if let Some(bounds) = bounds{
+                bounds.clamp(&mut vertex);
+            }
+            vertices.push(vertex);
+        }
+
+        let vertex_slices: Vec<&[T]> = vertices.iter().map(|p| &p[..]).collect();
+        check_vertices(&vertex_slices)?;
+        Ok(vertices)
+    }
+}
+
+// step_i * e_i for every axis.
+fn axis_offsets<T: Real>(steps: &[f64]) -> Result<Vec<Box<[T]>>, Error>{
+    if steps.iter().any(|step| !step.is_finite()){
+        return Err(Error::InvalidStep);
+    }
+    Ok((0..steps.len()).map(|i| (0..steps.len()).map(|k| if k == i { cast(steps[i]) } else { T::zero() }).collect()).collect())
+}
+
+fn bounds_contain<T: Real>(bounds: &Bounds, index: usize, value: T) -> bool{
+    cast::<T>(bounds.lower[index]) <= value && value <= cast(bounds.upper[index])
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn distance(a: &[f64], b: &[f64]) -> f64{
+        a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn keeps_the_guess_as_a_vertex(){
+        let guess = vec![1.0, 0.0, -3.0].into_boxed_slice();
+        for initial in [InitialSimplex::Axis(0.5), InitialSimplex::Regular(0.5), InitialSimplex::pfeffer(), InitialSimplex::Steps(vec![0.1, 1.0, 10.0].into_boxed_slice())]{
+            let vertices = initial.vertices(guess.clone(), None).unwrap();
+            assert_eq!(vertices.len(), 4);
+            assert_eq!(vertices[0], guess);
+        }
+        let pfeffer = InitialSimplex::pfeffer().vertices(guess.clone(), None).unwrap();
+        assert_eq!(pfeffer[1][..], [1.05, 0.0, -3.0]);
+        assert_eq!(pfeffer[2][..], [1.0, 0.00025, -3.0]);
+    }
+
+    #[test]
+    fn regular_edges_are_equal(){
+        let vertices = InitialSimplex::Regular(2.0).vertices(vec![0.0; 5].into_boxed_slice(), None::<&Bounds>).unwrap();
+        for i in 0..vertices.len(){
+            for j in 0..i{
+                assert!((distance(&vertices[i], &vertices[j]) - 2.0).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn steps_back_from_the_bounds(){
+        let bounds = Bounds::new(vec![0.0, 0.0], vec![1.0, 1.0]).unwrap();
+        let vertices = InitialSimplex::Axis(0.5).vertices(vec![1.0, 2.0].into_boxed_slice(), Some(&bounds)).unwrap();
+        assert_eq!(vertices, vec![vec![1.0, 1.0].into_boxed_slice(), vec![0.5, 1.0].into_boxed_slice(), vec![1.0, 0.5].into_boxed_slice()]);
+    }
+
+    #[test]
+    fn rejects_invalid_settings(){
+        let guess = || vec![0.0, 0.0].into_boxed_slice();
+        assert_eq!(InitialSimplex::Axis(f64::NAN).vertices(guess(), None).err(), Some(Error::InvalidStep));
+        assert_eq!(InitialSimplex::Regular(f64::INFINITY).vertices(guess(), None).err(), Some(Error::InvalidStep));
+        assert_eq!(InitialSimplex::Axis(0.0).vertices(guess(), None).err(), Some(Error::DegenerateSimplex));
+        assert_eq!(InitialSimplex::Steps(vec![1.0].into_boxed_slice()).vertices(guess(), None).err(), Some(Error::DimensionMismatch{ expected: 2, found: 1 }));
+    }
+}
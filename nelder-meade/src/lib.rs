@@ -0,0 +1,36 @@
+// The optimizers and their building blocks. main.rs is a command-line front end on top.
+// Struct literals spell out field names throughout the crate.
+#![allow(clippy::redundant_field_names)]
+
+pub mod example_functions;
+pub mod point;
+pub mod simplex;
+pub mod initial;
+mod helper_functions;
+pub mod real;
+pub mod error;
+pub mod nelder_meade;
+pub mod objective;
+pub mod bounds;
+pub mod constrained;
+pub mod coefficients;
+pub mod termination;
+pub mod result;
+pub mod restart;
+pub mod noise;
+pub mod annealing;
+pub mod observer;
+pub mod rng;
+pub mod multistart;
+pub mod benchmark;
+pub mod expression;
+pub mod cli;
+pub mod checkpoint;
+mod serde_float;
+pub mod ask_tell;
+pub mod optimizer;
+pub mod hooke_jeeves;
+pub mod multidirectional;
+pub mod powell;
+pub mod cma_es;
+pub mod subplex;
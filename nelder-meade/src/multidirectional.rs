@@ -0,0 +1,218 @@
+use std::time::Instant;
+
+use num_traits::Float;
+
+use crate::{simplex::{Simplex, Vertices}, nelder_meade::Move, objective::Objective, real::{Real, cast}, error::Error, bounds::Bounds, optimizer::{Optimizer, evaluate_points, feasible_guess}, termination::{Termination, StopReason}, result::{OptimizationResult, MoveCounts, IterationRecord}};
+
+// Torczon's multi-directional search (1989). Every iteration reflects all vertices through the
+// best one at once, then tries to expand or else contracts the whole simplex towards the best
+// vertex. The simplex keeps its shape, which gives the convergence guarantees Nelder-Mead lacks,
+// and the n new points of an iteration are independent, so objectives that prefer batches
+// evaluate them together. Moves are counted as reflect, expand and shrink.
+pub struct MultiDirectional<O, S: Vertices = Simplex> where O: Objective<S::Scalar>{
+    pub simplex: S,
+    pub func: O,
+    // best + expansion * (best - x_i)
+    pub expansion: f64,
+    // best + contraction * (x_i - best)
+    pub contraction: f64,
+    pub iterations: usize,
+    pub evaluations: usize,
+    pub moves: MoveCounts,
+    // Keep a per-iteration record of the best value and simplex diameter in run().
+    pub record_history: bool,
+    // See set_bounds.
+    bounds: Option<Bounds>,
+}
+
+impl<O: Objective<S::Scalar>, S: Vertices> MultiDirectional<O, S>{
+
+    pub fn new(simplex: S, func: O) -> MultiDirectional<O, S>{
+        MultiDirectional{
+            // the initial simplex was evaluated once per vertex
+            evaluations: simplex.vertex_count(),
+            simplex: simplex,
+            func: func,
+            bounds: None,
+            expansion: 2.0,
+            contraction: 0.5,
+            iterations: 0,
+            moves: MoveCounts::default(),
+            record_history: false
+        }
+    }
+
+    // Keeps every new point inside the box. The simplex is not moved, so build it inside the
+    // box too (see Simplex::from_guess).
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) -> Result<(), Error>{
+        if let Some(bounds) = &bounds{
+            if bounds.dimensions() != self.simplex.dimensions(){
+                return Err(Error::DimensionMismatch{ expected: self.simplex.dimensions(), found: bounds.dimensions() });
+            }
+        }
+        self.bounds = bounds;
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.bounds.as_ref()
+    }
+
+    // best + factor * (best - x_i) for every vertex except the best one.
+    fn trial_points(&self, best: usize, factor: S::Scalar) -> Vec<Box<[S::Scalar]>>{
+        let center = self.simplex.x(best);
+        (0..self.simplex.vertex_count()).filter(|i| *i != best).map(|i| {
+            let mut point: Box<[S::Scalar]> = self.simplex.x(i).into();
+            for k in 0..point.len(){
+                point[k] = center[k] + factor * (center[k] - point[k]);
+            }
+            if let Some(bounds) = &self.bounds{
+                bounds.clamp(&mut point);
+            }
+            point
+        }).collect()
+    }
+
+    fn evaluate(&mut self, points: &[Box<[S::Scalar]>]) -> Vec<S::Scalar>{
+        let slices: Vec<&[S::Scalar]> = points.iter().map(|p| &p[..]).collect();
+        evaluate_points(&mut self.func, &mut self.evaluations, &slices)
+    }
+
+    fn replace_all_except(&mut self, best: usize, points: Vec<Box<[S::Scalar]>>, values: Vec<S::Scalar>){
+        let mut others = (0..self.simplex.vertex_count()).filter(|i| *i != best);
+        for (point, value) in points.into_iter().zip(values){
+            let i = others.next().unwrap();
+            self.simplex.x_mut(i).copy_from_slice(&point);
+            self.simplex.set_value(i, value);
+        }
+    }
+
+    pub fn step(&mut self){
+        let best = self.simplex.best_index();
+        let best_value = self.simplex.value(best);
+        let smallest = |values: &[S::Scalar]| values.iter().fold(S::Scalar::infinity(), |smallest, v| smallest.min(*v));
+
+        let reflected = self.trial_points(best, cast(1.0));
+        let reflected_values = self.evaluate(&reflected);
+        if smallest(&reflected_values) < best_value{
+            let expanded = self.trial_points(best, cast(self.expansion));
+            let expanded_values = self.evaluate(&expanded);
+            if smallest(&expanded_values) < smallest(&reflected_values){
+                self.replace_all_except(best, expanded, expanded_values);
+                self.moves.record(Move::Expand);
+            } else {
+                self.replace_all_except(best, reflected, reflected_values);
+                self.moves.record(Move::Reflect);
+            }
+        } else {
+            let contracted = self.trial_points(best, -cast::<S::Scalar>(self.contraction));
+            let contracted_values = self.evaluate(&contracted);
+            self.replace_all_except(best, contracted, contracted_values);
+            self.moves.record(Move::Shrink);
+        }
+    }
+
+    pub fn run(&mut self, criteria: &Termination) -> OptimizationResult<S::Scalar>{
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+        let start_evaluations = self.evaluations;
+        let mut history: Option<Vec<IterationRecord<S::Scalar>>> = if self.record_history { Some(Vec::new()) } else { None };
+        let stop_reason: StopReason = loop {
+            if let Some(reason) = criteria.check(&self.simplex, self.iterations - start_iterations, self.evaluations - start_evaluations, start){
+                break reason;
+            }
+            self.step();
+            self.iterations += 1;
+            if let Some(history) = &mut history{
+                history.push(IterationRecord{
+                    iteration: self.iterations,
+                    best_value: self.simplex.value(self.simplex.best_index()),
+                    diameter: self.simplex.diameter()
+                });
+            }
+        };
+        OptimizationResult{
+            best: self.simplex.point(self.simplex.best_index()),
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: Some(self.moves),
+            restarts: 0,
+            history: history,
+            stop_reason: stop_reason
+        }
+    }
+}
+
+impl<O: Objective<T>, T: Real> MultiDirectional<O, Simplex<T>>{
+    // The simplex of Simplex::from_guess around the guess projected onto the bounds, kept inside
+    // them. Takes the same arguments as
+    // HookeJeeves::new and the other optimizers started from a guess.
+    pub fn from_guess(guess: Box<[T]>, step: T, bounds: Option<&Bounds>, mut func: O) -> Result<MultiDirectional<O, Simplex<T>>, Error>{
+        let guess = feasible_guess(guess, bounds, &func)?;
+        let simplex = Simplex::from_guess(guess, step, bounds, &mut func)?;
+        let mut multidirectional = MultiDirectional::new(simplex, func);
+        multidirectional.set_bounds(bounds.cloned())?;
+        Ok(multidirectional)
+    }
+}
+
+impl<O: Objective<S::Scalar>, S: Vertices> Optimizer for MultiDirectional<O, S>{
+    type Scalar = S::Scalar;
+
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<S::Scalar>{
+        MultiDirectional::run(self, criteria)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::objective::{Batched, BatchObjective};
+
+    // Counts the batches it is asked for.
+    struct Sphere{
+        batches: usize,
+    }
+
+    impl BatchObjective for Sphere{
+        fn evaluate_batch(&mut self, points: &[&[f64]]) -> Vec<f64>{
+            self.batches += 1;
+            points.iter().map(|x| x.iter().map(|xi| (xi - 1.0) * (xi - 1.0)).sum()).collect()
+        }
+    }
+
+    // Edge lengths relative to the first edge.
+    fn shape(simplex: &Simplex) -> Vec<f64>{
+        let mut edges = Vec::new();
+        for i in 0..simplex.points.len(){
+            for j in 0..i{
+                let x = &simplex.points[i].x;
+                let y = &simplex.points[j].x;
+                edges.push(x.iter().zip(y.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt());
+            }
+        }
+        edges.iter().map(|e| e / edges[0]).collect()
+    }
+
+    #[test]
+    fn minimizes_in_batches(){
+        let mut search = MultiDirectional::from_guess(vec![-2.0, 3.0, 0.5].into_boxed_slice(), 1.0, None, Batched(Sphere{ batches: 0 })).unwrap();
+        let result = search.run(&Termination::new().max_iterations(10_000).x_tol(1e-9));
+        assert_eq!(result.stop_reason, StopReason::StepTolerance);
+        assert!(result.best.x.iter().all(|x| (x - 1.0).abs() < 1e-6), "{:?}", result.best.x);
+        // the initial simplex and at most two batches per iteration
+        assert!(search.func.0.batches <= 1 + 2 * result.iterations);
+        let moves = result.moves.unwrap();
+        assert_eq!(moves.reflect + moves.expand + moves.shrink, result.iterations);
+    }
+
+    #[test]
+    fn keeps_the_shape_of_the_simplex(){
+        let mut search = MultiDirectional::from_guess(vec![-2.0, 3.0, 0.5].into_boxed_slice(), 1.0, None, Batched(Sphere{ batches: 0 })).unwrap();
+        let before = shape(&search.simplex);
+        search.run(&Termination::new().max_iterations(30));
+        for (a, b) in before.iter().zip(shape(&search.simplex)){
+            assert!((a - b).abs() < 1e-9, "{} {}", a, b);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{panic, thread};
+
+use crate::{nelder_meade::NelderMeade, error::Error, simplex::Simplex, point::Point, bounds::Bounds, rng::Rng, termination::Termination, result::OptimizationResult, helper_functions::distance};
+
+// Runs independent NelderMeade searches from space-filling starting points inside a box and
+// collects the distinct minima they converge to.
+pub struct MultiStart{
+    // Both the sampling box and the bounds enforced during every search. Must be finite.
+    pub bounds: Bounds,
+    pub starts: usize,
+    pub seed: u64,
+    // Initial simplex step as a fraction of the narrowest side of the box.
+    pub step_fraction: f64,
+    pub termination: Termination,
+    // Converged points closer than this are treated as the same minimum.
+    pub distinct_tol: f64,
+    pub parallel: bool,
+}
+
+pub struct Minimum{
+    pub point: Point,
+    // Number of starts that converged to this minimum.
+    pub hits: usize,
+    // The run that found the best representative of this minimum.
+    pub result: OptimizationResult,
+}
+
+// One sample per stratum in every coordinate, strata paired up by independent permutations.
+pub fn latin_hypercube(bounds: &Bounds, samples: usize, rng: &mut Rng) -> Vec<Box<[f64]>>{
+    let mut points: Vec<Box<[f64]>> = vec![vec![0.0; bounds.dimensions()].into_boxed_slice(); samples];
+    let mut strata: Vec<usize> = (0..samples).collect();
+    for d in 0..bounds.dimensions(){
+        rng.shuffle(&mut strata);
+        let width = bounds.upper[d] - bounds.lower[d];
+        for (point, stratum) in points.iter_mut().zip(strata.iter()){
+            let u = (*stratum as f64 + rng.next_f64()) / samples as f64;
+            point[d] = bounds.lower[d] + u * width;
+        }
+    }
+    points
+}
+
+impl MultiStart{
+    pub fn new(bounds: Bounds, starts: usize) -> MultiStart{
+        MultiStart{
+            bounds: bounds,
+            starts: starts,
+            seed: 0,
+            step_fraction: 0.1,
+            termination: Termination::default(),
+            distinct_tol: 1e-4,
+            parallel: true
+        }
+    }
+
+    fn search<F: Fn(&[f64]) -> f64 + Sync>(&self, start: Box<[f64]>, step: f64, func: &F) -> Result<OptimizationResult, Error>{
+        let mut objective = |x: &[f64]| func(x);
+        let simplex = Simplex::from_guess(start, step, Some(&self.bounds), &mut objective)?;
+        let mut nelder_meade = NelderMeade::adaptive(simplex, objective);
+        nelder_meade.set_bounds(Some(self.bounds.clone()))?;
+        Ok(nelder_meade.run(&self.termination))
+    }
+
+    // Minima ordered from best to worst. The same seed always gives the same starting points,
+    // whether or not the searches run in parallel. A start no search can begin from (say, the
+    // objective fails at every vertex of its simplex) is skipped; the run only returns its
+    // error if every start fails.
+    pub fn run<F: Fn(&[f64]) -> f64 + Sync>(&self, func: &F) -> Result<Vec<Minimum>, Error>{
+        let mut step = f64::INFINITY;
+        for d in 0..self.bounds.dimensions(){
+            let width = self.bounds.upper[d] - self.bounds.lower[d];
+            if !width.is_finite(){
+                return Err(Error::UnboundedCoordinate{ position: d });
+            }
+            step = step.min(width * self.step_fraction);
+        }
+
+        let mut rng = Rng::new(self.seed);
+        let starts = latin_hypercube(&self.bounds, self.starts, &mut rng);
+
+        let threads = if self.parallel { thread::available_parallelism().map(|n| n.get()).unwrap_or(1) } else { 1 };
+        let results: Vec<Result<OptimizationResult, Error>> = if threads <= 1 || starts.len() <= 1{
+            starts.into_iter().map(|start| self.search(start, step, func)).collect()
+        } else {
+            let chunk_size = starts.len().div_ceil(threads);
+            thread::scope(|scope| {
+                let handles: Vec<_> = starts.chunks(chunk_size)
+                    .map(|chunk| scope.spawn(move || chunk.iter().map(|start| self.search(start.clone(), step, func)).collect::<Vec<_>>()))
+                    .collect();
+                // a panicking objective panics here with its own payload, as it would without threads
+                handles.into_iter().flat_map(|handle| handle.join().unwrap_or_else(|payload| panic::resume_unwind(payload))).collect()
+            })
+        };
+
+        let mut failure: Option<Error> = None;
+        let mut results: Vec<OptimizationResult> = results.into_iter().filter_map(|result| match result{
+            Ok(result) => Some(result),
+            Err(error) => {
+                failure.get_or_insert(error);
+                None
+            }
+        }).collect();
+        if let (true, Some(error)) = (results.is_empty(), failure){
+            return Err(error);
+        }
+        results.sort_by(|a, b| a.best.value.total_cmp(&b.best.value));
+
+        let mut minima: Vec<Minimum> = Vec::new();
+        for result in results{
+            match minima.iter_mut().find(|m| distance(&m.point.x, &result.best.x).is_ok_and(|d| d <= self.distinct_tol)){
+                Some(minimum) => minimum.hits += 1,
+                None => minima.push(Minimum{
+                    point: result.best.clone(),
+                    hits: 1,
+                    result: result
+                })
+            }
+        }
+        Ok(minima)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Fails left of x0 = 0, smallest at (0.5, 0).
+    fn right_half(x: &[f64]) -> f64{
+        if x[0] < 0.0 { f64::NAN } else { (x[0] - 0.5).powi(2) + x[1] * x[1] }
+    }
+
+    #[test]
+    fn skips_the_starts_that_fail(){
+        let bounds = Bounds::new(vec![-1.0; 2], vec![1.0; 2]).unwrap();
+        let mut multistart = MultiStart::new(bounds, 8);
+        multistart.termination = Termination::new().max_iterations(2_000).x_tol(1e-9);
+        for parallel in [false, true]{
+            multistart.parallel = parallel;
+            let minima = multistart.run(&right_half).unwrap();
+            let hits: usize = minima.iter().map(|m| m.hits).sum();
+            assert!(0 < hits && hits < 8, "{}", hits);
+            assert!((minima[0].point.x[0] - 0.5).abs() < 1e-6 && minima[0].point.x[1].abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn fails_only_if_every_start_fails(){
+        let bounds = Bounds::new(vec![-1.0; 2], vec![1.0; 2]).unwrap();
+        let multistart = MultiStart::new(bounds, 4);
+        assert_eq!(multistart.run(&|_: &[f64]| f64::NAN).err(), Some(Error::NonFiniteStartValue));
+    }
+
+    #[test]
+    fn passes_on_the_panic_of_the_objective(){
+        let bounds = Bounds::new(vec![-1.0; 2], vec![1.0; 2]).unwrap();
+        let mut multistart = MultiStart::new(bounds, 4);
+        multistart.parallel = true;
+        let payload = match panic::catch_unwind(|| multistart.run(&|_: &[f64]| -> f64 { panic!("objective failed") })){
+            Err(payload) => payload,
+            Ok(_) => panic!("the objective panics")
+        };
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"objective failed"));
+    }
+
+    #[test]
+    fn samples_one_point_per_stratum(){
+        let bounds = Bounds::new(vec![0.0, -10.0], vec![1.0, 10.0]).unwrap();
+        let points = latin_hypercube(&bounds, 10, &mut Rng::new(3));
+        for d in 0..2{
+            let width = bounds.upper[d] - bounds.lower[d];
+            let mut strata: Vec<usize> = points.iter().map(|p| ((p[d] - bounds.lower[d]) / width * 10.0) as usize).collect();
+            strata.sort();
+            assert_eq!(strata, (0..10).collect::<Vec<usize>>());
+        }
+    }
+}
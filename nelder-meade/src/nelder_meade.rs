@@ -1,161 +1,888 @@
-use crate::{simplex::Simplex, helper_functions::{add_to_slice, multiply_by_const, sub_to_slice, distance, absolute_value}, point::Point};
-
-pub struct NelderMeade{
-    pub simplex: Simplex,
-    pub reflection: f64,
-    pub expansion: f64,
-    pub contraction: f64,
-    pub shrink: f64,
-    pub func: fn(&[f64]) -> f64,
-}
-
-impl NelderMeade{
-
-    fn centroid_without_index(&self, index: usize) -> Box<[f64]>{
-        let mut centroid: Box<[f64]> = vec![0.0; self.simplex.points.len() - 1].into_boxed_slice();
-        for i in 0..self.simplex.points.len() {
-            if i != index{
-                centroid = match add_to_slice(centroid, &self.simplex.points[i].x){
-                    Ok(s) => s,
-                    Err(str) => panic!("{}", str)
-                };
+use std::time::Instant;
+
+use num_traits::{Float, ToPrimitive};
+use serde::{Serialize, Deserialize};
+
+use crate::{simplex::{Simplex, Vertices, FixedSimplex, needed_points}, error::Error, point::Point, helper_functions::{add_assign, affine_combination, move_towards}, objective::{Objective, ObjectiveValue, Fixed, sanitize}, real::{Real, cast}, bounds::Bounds, coefficients::Coefficients, termination::{Termination, StopReason}, restart::RestartPolicy, noise::{NoisePolicy, Samples, pooled_variance}, annealing::Annealing, rng::Rng, observer::{Observer, Control}, optimizer::feasible_guess, checkpoint::Checkpoint, result::{OptimizationResult, MoveCounts, IterationRecord}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Move{
+    Reflect,
+    Expand,
+    OutsideContract,
+    InsideContract,
+    Shrink,
+}
+
+// What the value of the reflected point calls for. values holds the values of the
+// [smallest, second_largest, largest] vertices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AfterReflection{
+    Accept,
+    Expand,
+    OutsideContract,
+    InsideContract,
+}
+
+pub fn after_reflection<T: Real>(reflected_value: T, values: &[T; 3]) -> AfterReflection{
+    if reflected_value < values[1] && values[0] < reflected_value{
+        AfterReflection::Accept
+    } else if reflected_value < values[0]{
+        AfterReflection::Expand
+    } else if reflected_value < values[2]{
+        AfterReflection::OutsideContract
+    } else {
+        AfterReflection::InsideContract
+    }
+}
+
+// The point of a step that needs a value next. Shrink stands for every vertex except the
+// smallest one, after they were moved towards it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Pending{
+    Reflection,
+    Expansion,
+    OutsideContraction,
+    InsideContraction,
+    Shrink,
+}
+
+// Preallocated buffers for step().
+struct Workspace<T>{
+    // Sum of all vertices, updated as vertices are replaced.
+    sum: Box<[T]>,
+    centroid: Box<[T]>,
+    reflected: Box<[T]>,
+    // expansion and contraction points; the best vertex during a shrink
+    trial: Box<[T]>,
+    // vertex replacements since sum was last recomputed
+    updates: usize,
+    // Gram-Schmidt scratch space for the degeneracy check
+    basis: Box<[T]>,
+    edge: Box<[T]>,
+    // With a noise policy: evaluations per new point in the current step, and the evaluations
+    // behind the reflected and trial points.
+    sample_count: usize,
+    reflected_samples: Samples<T>,
+    trial_samples: Samples<T>,
+    // With annealing: the vertex values plus their thermal fluctuations, and whether they were
+    // already drawn for the current step.
+    fluctuated: Box<[T]>,
+    drawn: bool,
+    // The vertices of the current step, the vertex values the new points are compared with, and
+    // the value of the reflected point as evaluated and as compared.
+    needed: [usize; 3],
+    compared: [T; 3],
+    reflected_value: T,
+    reflected_compared: T,
+}
+
+impl<T: Real> Workspace<T>{
+    fn new<S: Vertices<Scalar = T>>(simplex: &S) -> Workspace<T>{
+        let dimensions = simplex.dimensions();
+        let mut workspace = Workspace{
+            sum: vec![T::zero(); dimensions].into_boxed_slice(),
+            centroid: vec![T::zero(); dimensions].into_boxed_slice(),
+            reflected: vec![T::zero(); dimensions].into_boxed_slice(),
+            trial: vec![T::zero(); dimensions].into_boxed_slice(),
+            updates: 0,
+            basis: vec![T::zero(); dimensions * dimensions].into_boxed_slice(),
+            edge: vec![T::zero(); dimensions].into_boxed_slice(),
+            sample_count: 1,
+            reflected_samples: Samples::new(),
+            trial_samples: Samples::new(),
+            fluctuated: vec![T::zero(); simplex.vertex_count()].into_boxed_slice(),
+            drawn: false,
+            needed: [0; 3],
+            compared: [T::zero(); 3],
+            reflected_value: T::zero(),
+            reflected_compared: T::zero()
+        };
+        workspace.refresh(simplex);
+        workspace
+    }
+
+    fn refresh<S: Vertices<Scalar = T>>(&mut self, simplex: &S){
+        if self.sum.len() != simplex.dimensions(){
+            *self = Workspace::new(simplex);
+            return;
+        }
+        self.sum.fill(T::zero());
+        for i in 0..simplex.vertex_count(){
+            add_assign(&mut self.sum, simplex.x(i));
+        }
+        self.updates = 0;
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Trial{
+    Reflected,
+    Other,
+}
+
+fn evaluate<T: Real, O: Objective<T>>(func: &mut O, evaluations: &mut usize, x: &[T]) -> T{
+    *evaluations += 1;
+    sanitize(func.evaluate(x))
+}
+
+// count evaluations of x, as one batch if the objective prefers batches.
+fn sample<T: Real, O: Objective<T>>(func: &mut O, evaluations: &mut usize, x: &[T], count: usize) -> Samples<T>{
+    let mut samples = Samples::new();
+    if func.prefers_batches(){
+        let points: Vec<&[T]> = vec![x; count];
+        let mut values = func.evaluate_batch(&points).into_iter();
+        *evaluations += count;
+        for _ in 0..count{
+            samples.add(values.next().map_or(T::infinity(), sanitize));
+        }
+    } else {
+        for _ in 0..count{
+            samples.add(evaluate(func, evaluations, x));
+        }
+    }
+    samples
+}
+
+// Keeps x if it is better than the best point so far.
+fn remember<T: Real>(best_ever: &mut Option<Point<T>>, x: &[T], value: T){
+    match best_ever{
+        Some(best) if value >= best.value => {},
+        Some(best) => {
+            best.x.copy_from_slice(x);
+            best.value = value;
+        },
+        None => *best_ever = Some(Point{x: x.into(), value: value})
+    }
+}
+
+// S is Simplex by default; FixedSimplex<N> keeps the vertices on the stack. Simplex<f32> and
+// FixedSimplex<N, f32> run the whole search in single precision.
+pub struct NelderMeade<O, S: Vertices = Simplex> where O: Objective<S::Scalar>{
+    // If you change the simplex by hand between calls to step(), call refresh_workspace().
+    pub simplex: S,
+    pub coefficients: Coefficients,
+    pub func: O,
+    pub iterations: usize,
+    pub evaluations: usize,
+    pub moves: MoveCounts,
+    // Keep a per-iteration record of the best value and simplex diameter in run().
+    pub record_history: bool,
+    pub restart: RestartPolicy,
+    pub restarts: usize,
+    // For noisy objectives, see NoisePolicy. None takes every value at face value.
+    pub noise: Option<NoisePolicy>,
+    // Simulated annealing for global exploration, see Annealing. None is plain Nelder-Mead.
+    pub annealing: Option<Annealing>,
+    // See set_bounds. Set directly only where the box is built from the same coordinates as the
+    // simplex, so that their dimensions agree.
+    pub(crate) bounds: Option<Bounds>,
+    initial_step: S::Scalar,
+    workspace: Workspace<S::Scalar>,
+    // With a noise policy, the evaluations behind every vertex value. Rebuilt from the vertex
+    // values (as single evaluations) when the number of vertices does not match.
+    vertex_samples: Vec<Samples<S::Scalar>>,
+    // With annealing: the generator of the fluctuations (seeded on the first step) and the best
+    // point evaluated so far.
+    annealing_rng: Option<Rng>,
+    best_ever: Option<Point<S::Scalar>>,
+}
+
+impl<O: Objective<S::Scalar>, S: Vertices> NelderMeade<O, S>{
+
+    pub fn new(simplex: S, func: O) -> NelderMeade<O, S>{
+        NelderMeade::with_coefficients(simplex, func, Coefficients::standard())
+    }
+
+    // Picks the Gao-Han coefficients for the dimension of the simplex.
+    pub fn adaptive(simplex: S, func: O) -> NelderMeade<O, S>{
+        let coefficients = Coefficients::adaptive(simplex.dimensions());
+        NelderMeade::with_coefficients(simplex, func, coefficients)
+    }
+
+    pub fn with_coefficients(simplex: S, func: O, coefficients: Coefficients) -> NelderMeade<O, S>{
+        NelderMeade{
+            // the initial simplex was evaluated once per vertex
+            evaluations: simplex.vertex_count(),
+            initial_step: simplex.diameter(),
+            workspace: Workspace::new(&simplex),
+            simplex: simplex,
+            coefficients: coefficients,
+            func: func,
+            bounds: None,
+            iterations: 0,
+            moves: MoveCounts::default(),
+            record_history: false,
+            restart: RestartPolicy::default(),
+            restarts: 0,
+            noise: None,
+            annealing: None,
+            vertex_samples: Vec::new(),
+            annealing_rng: None,
+            best_ever: None
+        }
+    }
+
+    pub fn needed_points(&self) -> [usize; 3]{
+        self.simplex.needed_points()
+    }
+
+    // Keeps every new point inside the box. The simplex is not moved, so build it inside the
+    // box too (see Simplex::from_guess).
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) -> Result<(), Error>{
+        if let Some(bounds) = &bounds{
+            if bounds.dimensions() != self.simplex.dimensions(){
+                return Err(Error::DimensionMismatch{ expected: self.simplex.dimensions(), found: bounds.dimensions() });
             }
         }
-        multiply_by_const(centroid, 1.0/((self.simplex.points.len() - 1) as f64))
+        self.bounds = bounds;
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.bounds.as_ref()
+    }
+
+    // The best point evaluated since annealing was switched on, which the simplex may have
+    // moved away from. None without annealing.
+    pub fn best_ever(&self) -> Option<&Point<S::Scalar>>{
+        self.best_ever.as_ref()
+    }
+
+    // The vertices for the next step: ranked by their fluctuated values with annealing.
+    fn next_indices(&mut self) -> [usize; 3]{
+        match self.annealing{
+            Some(annealing) => {
+                self.prepare_annealing(annealing);
+                let fluctuated = &self.workspace.fluctuated;
+                needed_points(fluctuated.len(), |i| fluctuated[i])
+            },
+            None => self.simplex.needed_points()
+        }
+    }
+
+    // Recomputes the vertex sum from scratch. Call this after changing `simplex` by hand and
+    // before calling step() directly; run() and iterate_n_times() do it on their own.
+    pub fn refresh_workspace(&mut self){
+        self.workspace.refresh(&self.simplex);
     }
-    
-    fn centroid(&self) -> Box<[f64]>{
-        let mut centroid = self.simplex.points[0].x.clone();
-        for i in 1..self.simplex.points.len() {
-            centroid = match add_to_slice(centroid, &self.simplex.points[i].x){
-                Ok(s) => s,
-                Err(str) => panic!("{}", str)
+
+    // Swaps the trial point into the simplex in place of the vertex at index. The old
+    // coordinates end up in the trial buffer, which is reused for the next trial point.
+    fn replace_vertex(&mut self, index: usize, trial: Trial, value: S::Scalar){
+        let buffer = match trial{
+            Trial::Reflected => &mut self.workspace.reflected,
+            Trial::Other => &mut self.workspace.trial
+        };
+        self.simplex.x_mut(index).swap_with_slice(buffer);
+        self.simplex.set_value(index, value);
+        if self.noise.is_some(){
+            self.vertex_samples[index] = match trial{
+                Trial::Reflected => self.workspace.reflected_samples,
+                Trial::Other => self.workspace.trial_samples
             };
         }
-        multiply_by_const(centroid, 1.0/(self.simplex.points.len() as f64))
+        let old: &[S::Scalar] = match trial{
+            Trial::Reflected => &self.workspace.reflected,
+            Trial::Other => &self.workspace.trial
+        };
+        let new = self.simplex.x(index);
+        for k in 0..self.workspace.sum.len(){
+            self.workspace.sum[k] += new[k] - old[k];
+        }
+        self.workspace.updates += 1;
+        // keep rounding errors of the running sum from piling up
+        if self.workspace.updates >= self.simplex.vertex_count(){
+            self.workspace.refresh(&self.simplex);
+        }
+    }
+
+    // Evaluates every vertex except the one at kept, after a shrink or restart moved them.
+    fn evaluate_all_except(&mut self, kept: usize){
+        let repeats = if self.noise.is_some() { self.workspace.sample_count } else { 1 };
+        if self.func.prefers_batches(){
+            let points: Vec<&[S::Scalar]> = (0..self.simplex.vertex_count()).filter(|i| *i != kept)
+                .flat_map(|i| std::iter::repeat_n(self.simplex.x(i), repeats)).collect();
+            let values = self.func.evaluate_batch(&points);
+            self.evaluations += points.len();
+            let mut values = values.into_iter();
+            for i in 0..self.simplex.vertex_count(){
+                if i != kept{
+                    let mut samples = Samples::new();
+                    for _ in 0..repeats{
+                        // a batch objective that returned too few values rejects the rest
+                        samples.add(values.next().map_or(S::Scalar::infinity(), sanitize));
+                    }
+                    self.set_samples(i, samples);
+                }
+            }
+        } else {
+            for i in 0..self.simplex.vertex_count(){
+                if i != kept{
+                    let samples = sample(&mut self.func, &mut self.evaluations, self.simplex.x(i), repeats);
+                    self.set_samples(i, samples);
+                }
+            }
+        }
+        if self.annealing.is_some(){
+            for i in 0..self.simplex.vertex_count(){
+                remember(&mut self.best_ever, self.simplex.x(i), self.simplex.value(i));
+            }
+        }
+        self.workspace.refresh(&self.simplex);
     }
-    
-    pub fn needed_points(&self) -> [usize; 3]{
-        // needed_indices: [smallest, second_largest, largest]
-        let mut needed_points: [usize; 3] = [0,1,2];
-        needed_points.sort_by(|a,b| self.simplex.points[*a].value.partial_cmp(&self.simplex.points[*b].value).unwrap());
 
-        for i in 3..self.simplex.points.len(){
-            if self.simplex.points[i].value > self.simplex.points[needed_points[2]].value{
-                needed_points[1] = needed_points[2];
-                needed_points[2] = i;
-            } else if self.simplex.points[i].value > self.simplex.points[needed_points[1]].value{
-                needed_points[1] = i;
-            } else if self.simplex.points[i].value < self.simplex.points[needed_points[0]].value{
-                needed_points[0] = i;
+    fn set_samples(&mut self, index: usize, samples: Samples<S::Scalar>){
+        self.simplex.set_value(index, samples.mean);
+        if self.noise.is_some(){
+            self.vertex_samples[index] = samples;
+        }
+    }
+
+    // Evaluates the reflected point or the trial point, averaging over several evaluations
+    // with a noise policy.
+    fn evaluate_trial(&mut self, trial: Trial) -> S::Scalar{
+        let x = match trial{
+            Trial::Reflected => &self.workspace.reflected,
+            Trial::Other => &self.workspace.trial
+        };
+        let value = if self.noise.is_none(){
+            evaluate(&mut self.func, &mut self.evaluations, x)
+        } else {
+            let samples = sample(&mut self.func, &mut self.evaluations, x, self.workspace.sample_count);
+            match trial{
+                Trial::Reflected => self.workspace.reflected_samples = samples,
+                Trial::Other => self.workspace.trial_samples = samples
             }
+            samples.mean
+        };
+        if self.annealing.is_some(){
+            remember(&mut self.best_ever, x, value);
         }
-        needed_points
+        value
+    }
+
+    // The value of the reflected or trial point minus its confidence margin (see NoisePolicy).
+    fn with_margin(&self, trial: Trial, value: S::Scalar) -> S::Scalar{
+        let confidence = match &self.noise{
+            Some(policy) if policy.confidence > 0.0 => policy.confidence,
+            _ => return value
+        };
+        let samples = match trial{
+            Trial::Reflected => &self.workspace.reflected_samples,
+            Trial::Other => &self.workspace.trial_samples
+        };
+        let variance = pooled_variance(self.vertex_samples.iter().chain(std::iter::once(samples)));
+        value - cast::<S::Scalar>(confidence) * (variance / cast(samples.count as f64)).sqrt()
     }
 
-    fn reflect(&self, centroid: &[f64], largest_index: usize) -> Box<[f64]>{
-        let mut reflected_point = sub_to_slice(centroid.clone().into(), &self.simplex.points[largest_index].x).unwrap();
-        reflected_point = multiply_by_const(reflected_point, self.reflection);
-        add_to_slice(reflected_point, centroid).unwrap()
+    // -T ln(u) at the current temperature, u uniform in (0, 1]. Zero without annealing.
+    fn fluctuation(&mut self) -> S::Scalar{
+        let (annealing, rng) = match (&self.annealing, &mut self.annealing_rng){
+            (Some(annealing), Some(rng)) => (annealing, rng),
+            _ => return cast(0.0)
+        };
+        let u = 1.0 - rng.next_f64();
+        cast(-annealing.temperature(self.iterations) * u.ln())
     }
 
-    fn expansion(&self, centroid: &[f64], reflection: &[f64]) -> Box<[f64]>{
-        let mut expansion_point: Box<[f64]> = sub_to_slice( reflection.clone().into(), centroid).unwrap();
-        expansion_point = multiply_by_const(expansion_point, self.expansion);
-        add_to_slice(expansion_point, centroid).unwrap()
+    // Seeds the fluctuations, starts the best point so far from the simplex and, once per
+    // step, draws the fluctuated vertex values.
+    fn prepare_annealing(&mut self, annealing: Annealing){
+        if self.annealing_rng.is_none(){
+            self.annealing_rng = Some(Rng::new(annealing.seed));
+        }
+        if self.best_ever.is_none(){
+            let best = self.simplex.best_index();
+            remember(&mut self.best_ever, self.simplex.x(best), self.simplex.value(best));
+        }
+        if self.workspace.drawn{
+            return;
+        }
+        if self.workspace.fluctuated.len() != self.simplex.vertex_count(){
+            self.workspace.fluctuated = vec![cast(0.0); self.simplex.vertex_count()].into_boxed_slice();
+        }
+        for i in 0..self.simplex.vertex_count(){
+            self.workspace.fluctuated[i] = self.simplex.value(i) + self.fluctuation();
+        }
+        self.workspace.drawn = true;
     }
 
-    fn contraction(&self, centroid: &[f64], point: &[f64]) -> Box<[f64]>{
-        let mut contraction: Box<[f64]> = sub_to_slice(centroid.clone().into(), point).unwrap();
-        contraction = multiply_by_const(contraction, self.contraction * -1.0);
-        add_to_slice(contraction, centroid).unwrap()
+    // Sets up the noise bookkeeping for a step: samples for every vertex and the number of
+    // evaluations per new point at the current simplex size.
+    fn prepare_noise(&mut self, policy: NoisePolicy){
+        if self.vertex_samples.len() != self.simplex.vertex_count(){
+            self.vertex_samples = (0..self.simplex.vertex_count()).map(|i| Samples::single(self.simplex.value(i))).collect();
+        }
+        let initial = self.initial_step.to_f64().unwrap_or(0.0);
+        let diameter = self.simplex.diameter().to_f64().unwrap_or(0.0);
+        self.workspace.sample_count = policy.samples_for(initial, diameter);
     }
 
-    fn shrink(&self, point: &[f64], smallest: &[f64]) -> Box<[f64]>{
-        let mut shrink: Box<[f64]> = sub_to_slice(point.clone().into(), smallest).unwrap();
-        shrink = multiply_by_const(shrink, self.shrink);
-        add_to_slice(shrink, smallest).unwrap()
+    // Adds evaluations to the best vertex, so that a value that was lucky gets corrected.
+    fn resample_best(&mut self, policy: NoisePolicy){
+        if policy.resample_best == 0{
+            return;
+        }
+        let best = self.simplex.best_index();
+        let extra = sample(&mut self.func, &mut self.evaluations, self.simplex.x(best), policy.resample_best);
+        self.vertex_samples[best].merge(&extra);
+        self.simplex.set_value(best, self.vertex_samples[best].mean);
     }
 
-    fn shrink_all(&mut self, smallest_index: usize){
-        for i in 0..self.simplex.points.len(){
+    // Moves every vertex except the one at smallest_index towards it. The moved vertices still
+    // need their values.
+    fn shrink_towards(&mut self, smallest_index: usize){
+        self.workspace.trial.copy_from_slice(self.simplex.x(smallest_index));
+        for i in 0..self.simplex.vertex_count(){
             if i != smallest_index{
-                let shrunk_point = self.shrink(&self.simplex.points[i].x, &self.simplex.points[smallest_index].x);
-                let shrunk_value = (self.func)(&shrunk_point);
-                self.simplex.points[i] = Point{
-                    x: shrunk_point,
-                    value: shrunk_value 
+                move_towards(self.simplex.x_mut(i), &self.workspace.trial, cast(self.coefficients.shrink));
+                if let Some(bounds) = &self.bounds{
+                    bounds.clamp(self.simplex.x_mut(i));
                 }
             }
         }
     }
 
-    pub fn step(&mut self, needed_indices: &[usize; 3]){
+    // Sets up a step for the vertices at needed_indices: the centroid of all vertices but the
+    // largest, the reflected point, and the vertex values the new points are compared with.
+    fn begin_step(&mut self, needed_indices: &[usize; 3]){
+        if self.workspace.sum.len() != self.simplex.dimensions(){
+            self.workspace = Workspace::new(&self.simplex);
+        }
+        if let Some(policy) = self.noise{
+            self.prepare_noise(policy);
+        }
+        if let Some(annealing) = self.annealing{
+            self.prepare_annealing(annealing);
+        }
+        let [smallest, second_largest, largest] = *needed_indices;
 
-        let centroid = self.centroid_without_index(needed_indices[2]);
-        let reflected_point = self.reflect(&centroid, needed_indices[2]);
-        let reflected_value = (self.func)(&reflected_point);
-        if reflected_value < self.simplex.points[needed_indices[1]].value && self.simplex.points[needed_indices[0]].value < reflected_value{
-            self.simplex.points[needed_indices[2]] = Point{x: reflected_point, value: reflected_value};
+        let others: S::Scalar = cast((self.simplex.vertex_count() - 1) as f64);
+        let worst = self.simplex.x(largest);
+        for ((centroid, sum), worst) in self.workspace.centroid.iter_mut().zip(self.workspace.sum.iter()).zip(worst){
+            *centroid = (*sum - *worst) / others;
+        }
+        affine_combination(&mut self.workspace.reflected, &self.workspace.centroid, worst, -cast::<S::Scalar>(self.coefficients.reflection));
+        if let Some(bounds) = &self.bounds{
+            bounds.clamp(&mut self.workspace.reflected);
+        }
+        // with annealing, new points are compared by their value minus a fluctuation and the
+        // vertices by their fluctuated values
+        self.workspace.compared = if self.annealing.is_some(){
+            let fluctuated = &self.workspace.fluctuated;
+            [fluctuated[smallest], fluctuated[second_largest], fluctuated[largest]]
+        } else {
+            [self.simplex.value(smallest), self.simplex.value(second_largest), self.simplex.value(largest)]
+        };
+        self.workspace.needed = *needed_indices;
+    }
 
-        // expansion
-        } else if reflected_value < self.simplex.points[needed_indices[0]].value {
-            let expansion_point = self.expansion(&centroid, &reflected_point);
-            let expansion_value = (self.func)(&expansion_point);
-            if expansion_value > reflected_value{
-                self.simplex.points[needed_indices[2]] = Point{x: reflected_point, value: reflected_value};
+    // Takes the value of the point that pending stands for. Ok with the move once the step is
+    // complete, Err with the point that needs a value next otherwise. A shrink is complete once
+    // the moved vertices have their values.
+    fn advance(&mut self, pending: Pending, value: S::Scalar) -> Result<Move, Pending>{
+        let [smallest, _, largest] = self.workspace.needed;
+        match pending{
+            Pending::Reflection => {
+                let reflected_compared = value - self.fluctuation();
+                self.workspace.reflected_value = value;
+                self.workspace.reflected_compared = reflected_compared;
+                match after_reflection(self.with_margin(Trial::Reflected, reflected_compared), &self.workspace.compared){
+                    AfterReflection::Accept => {
+                        self.replace_vertex(largest, Trial::Reflected, value);
+                        Ok(Move::Reflect)
+                    },
+                    AfterReflection::Expand => {
+                        affine_combination(&mut self.workspace.trial, &self.workspace.centroid, &self.workspace.reflected, cast(self.coefficients.expansion));
+                        if let Some(bounds) = &self.bounds{
+                            bounds.clamp(&mut self.workspace.trial);
+                        }
+                        Err(Pending::Expansion)
+                    },
+                    AfterReflection::OutsideContract => {
+                        affine_combination(&mut self.workspace.trial, &self.workspace.centroid, &self.workspace.reflected, cast(self.coefficients.contraction));
+                        if let Some(bounds) = &self.bounds{
+                            bounds.clamp(&mut self.workspace.trial);
+                        }
+                        Err(Pending::OutsideContraction)
+                    },
+                    AfterReflection::InsideContract => {
+                        affine_combination(&mut self.workspace.trial, &self.workspace.centroid, self.simplex.x(largest), cast(self.coefficients.contraction));
+                        if let Some(bounds) = &self.bounds{
+                            bounds.clamp(&mut self.workspace.trial);
+                        }
+                        Err(Pending::InsideContraction)
+                    }
+                }
+            },
+            Pending::Expansion => {
+                if self.with_margin(Trial::Other, value) - self.fluctuation() > self.workspace.reflected_compared{
+                    self.replace_vertex(largest, Trial::Reflected, self.workspace.reflected_value);
+                    Ok(Move::Reflect)
+                } else {
+                    self.replace_vertex(largest, Trial::Other, value);
+                    Ok(Move::Expand)
+                }
+            },
+            Pending::OutsideContraction => {
+                if self.with_margin(Trial::Other, value) - self.fluctuation() < self.workspace.reflected_compared{
+                    self.replace_vertex(largest, Trial::Other, value);
+                    Ok(Move::OutsideContract)
+                } else {
+                    self.shrink_towards(smallest);
+                    Err(Pending::Shrink)
+                }
+            },
+            Pending::InsideContraction => {
+                if self.with_margin(Trial::Other, value) - self.fluctuation() < self.workspace.compared[2]{
+                    self.replace_vertex(largest, Trial::Other, value);
+                    Ok(Move::InsideContract)
+                } else {
+                    self.shrink_towards(smallest);
+                    Err(Pending::Shrink)
+                }
+            },
+            Pending::Shrink => Ok(Move::Shrink)
+        }
+    }
 
-            }else {
-                self.simplex.points[needed_indices[2]] = Point{x: expansion_point, value: expansion_value};
+    fn finish_step(&mut self, taken: Move) -> Move{
+        if let Some(policy) = self.noise{
+            self.resample_best(policy);
+        }
+        self.workspace.drawn = false;
+        self.moves.record(taken);
+        taken
+    }
+
+    // Does not allocate (apart from shrink steps with an objective that prefers batches): the
+    // centroid comes from the running vertex sum and trial points live in preallocated buffers.
+    pub fn step(&mut self, needed_indices: &[usize; 3]) -> Move{
+        self.begin_step(needed_indices);
+        let mut pending = Pending::Reflection;
+        let taken = loop {
+            let advanced = match pending{
+                Pending::Reflection => {
+                    let value = self.evaluate_trial(Trial::Reflected);
+                    self.advance(pending, value)
+                },
+                Pending::Shrink => {
+                    self.evaluate_all_except(needed_indices[0]);
+                    Ok(Move::Shrink)
+                },
+                _ => {
+                    let value = self.evaluate_trial(Trial::Other);
+                    self.advance(pending, value)
+                }
+            };
+            match advanced{
+                Ok(taken) => break taken,
+                Err(next) => pending = next
             }
-        // outer contraction
-        } else if reflected_value < self.simplex.points[needed_indices[2]].value {
-            let outer_contraction = self.contraction(&centroid, &reflected_point);
-            let outer_contraction_value: f64 = (self.func)(&outer_contraction);
-            if outer_contraction_value < reflected_value{
-                self.simplex.points[needed_indices[2]] = Point{x: outer_contraction, value: outer_contraction_value};
+        };
+        self.finish_step(taken)
+    }
+
+    // step() for a driver that evaluates the points itself (see AskTell): starts a step on the
+    // vertices chosen like iterate_n_times() does. The points that need a value are
+    // pending_points(Pending::Reflection).
+    pub(crate) fn start_step(&mut self) -> Pending{
+        let needed_indices = self.next_indices();
+        self.begin_step(&needed_indices);
+        Pending::Reflection
+    }
+
+    pub(crate) fn pending_points(&self, pending: Pending) -> Vec<Box<[S::Scalar]>>{
+        match pending{
+            Pending::Reflection => vec![self.workspace.reflected.clone()],
+            Pending::Expansion | Pending::OutsideContraction | Pending::InsideContraction => vec![self.workspace.trial.clone()],
+            Pending::Shrink => (0..self.simplex.vertex_count()).filter(|i| *i != self.workspace.needed[0]).map(|i| self.simplex.x(i).into()).collect()
+        }
+    }
+
+    // Takes the values of pending_points(pending), in the same order, and counts them as
+    // evaluations. Ok with the move once the step is complete, the next pending points otherwise.
+    pub(crate) fn continue_step(&mut self, pending: Pending, values: &[S::Scalar]) -> Result<Move, Pending>{
+        self.evaluations += values.len();
+        let advanced = match pending{
+            Pending::Shrink => {
+                let kept = self.workspace.needed[0];
+                let mut values = values.iter();
+                for i in 0..self.simplex.vertex_count(){
+                    if i != kept{
+                        let value = values.next().map_or(S::Scalar::infinity(), |value| sanitize(*value));
+                        self.set_samples(i, Samples::single(value));
+                    }
+                }
+                if self.annealing.is_some(){
+                    for i in 0..self.simplex.vertex_count(){
+                        remember(&mut self.best_ever, self.simplex.x(i), self.simplex.value(i));
+                    }
+                }
+                self.workspace.refresh(&self.simplex);
+                Ok(Move::Shrink)
+            },
+            _ => {
+                let value = values.first().map_or(S::Scalar::infinity(), |value| sanitize(*value));
+                let trial = if pending == Pending::Reflection { Trial::Reflected } else { Trial::Other };
+                let x = match trial{
+                    Trial::Reflected => {
+                        self.workspace.reflected_samples = Samples::single(value);
+                        &self.workspace.reflected
+                    },
+                    Trial::Other => {
+                        self.workspace.trial_samples = Samples::single(value);
+                        &self.workspace.trial
+                    }
+                };
+                if self.annealing.is_some(){
+                    remember(&mut self.best_ever, x, value);
+                }
+                self.advance(pending, value)
             }
-            else {
-                self.shrink_all(needed_indices[0]);
+        };
+        advanced.map(|taken| self.finish_step(taken))
+    }
+
+    // Replaces the simplex by a right-angled one around the best vertex. The best vertex is kept,
+    // so a restart never loses progress.
+    fn restart_simplex(&mut self){
+        let step = self.restart.step.map(cast).unwrap_or(self.initial_step);
+        let best = self.simplex.best_index();
+        self.workspace.trial.copy_from_slice(self.simplex.x(best));
+        let mut axis = 0;
+        for i in 0..self.simplex.vertex_count(){
+            if i == best{
+                continue;
             }
-        // inner contraction
-        } else if reflected_value > self.simplex.points[needed_indices[2]].value {
-            let inner_contraction: Box<[f64]> = self.contraction(&centroid, &self.simplex.points[needed_indices[2]].x);
-            let inner_contraction_value: f64 = (self.func)(&inner_contraction);
-            if inner_contraction_value < self.simplex.points[needed_indices[2]].value{
-                self.simplex.points[needed_indices[2]] = Point{x: inner_contraction, value: inner_contraction_value}; 
+            let x = self.simplex.x_mut(i);
+            x.copy_from_slice(&self.workspace.trial);
+            x[axis] += step;
+            if let Some(bounds) = &self.bounds{
+                bounds.clamp(x);
             }
-            else {
-                self.shrink_all(needed_indices[0]);
+            // pinned against an upper bound, step the other way
+            if x[axis] == self.workspace.trial[axis]{
+                x[axis] = self.workspace.trial[axis] - step;
+                if let Some(bounds) = &self.bounds{
+                    bounds.clamp(x);
+                }
             }
+            axis += 1;
         }
-
+        self.evaluate_all_except(best);
+        self.restarts += 1;
     }
 
     pub fn iterate_n_times(&mut self, n: usize){
-        for i in 0..n{
-            self.step(&self.needed_points())
+        self.refresh_workspace();
+        for _ in 0..n{
+            let needed_indices = self.next_indices();
+            self.step(&needed_indices);
+            self.iterations += 1;
         }
     }
 
-    pub fn iterate_until_x_tol(&mut self, x_tol: f64){
-        let mut distance_change = x_tol + 1.0;
-        while distance_change > x_tol{
-            let needed_points = self.needed_points();
-            let previous_worst = self.simplex.points[needed_points[2]].x.clone();
-            self.step(&needed_points);
-            distance_change = distance(&previous_worst, &self.simplex.points[needed_points[2]].x).unwrap();
+    // Steps until one of the criteria is met. Counters in the result cover the whole
+    // lifetime of the optimizer, not just this run.
+    pub fn run(&mut self, criteria: &Termination) -> OptimizationResult<S::Scalar>{
+        self.run_observed(criteria, &mut |_: usize, _: Move, _: &S| Control::Continue)
+    }
+
+    // Like run, but calls the observer after every step. Returning Control::Stop ends the run.
+    pub fn run_observed<B: Observer<S> + ?Sized>(&mut self, criteria: &Termination, observer: &mut B) -> OptimizationResult<S::Scalar>{
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+        let start_evaluations = self.evaluations;
+        self.refresh_workspace();
+        let mut history: Option<Vec<IterationRecord<S::Scalar>>> = if self.record_history { Some(Vec::new()) } else { None };
+        let mut best_value = self.simplex.value(self.simplex.best_index());
+        let mut stalled_iterations = 0;
+        let stop_reason = loop {
+            if let Some(reason) = criteria.check(&self.simplex, self.iterations - start_iterations, self.evaluations - start_evaluations, start){
+                break reason;
+            }
+            let needed_indices = self.next_indices();
+            let taken = self.step(&needed_indices);
+            self.iterations += 1;
+            if let Some(history) = &mut history{
+                history.push(IterationRecord{
+                    iteration: self.iterations,
+                    best_value: self.simplex.value(self.simplex.best_index()),
+                    diameter: self.simplex.diameter()
+                });
+            }
+            if observer.observe(self.iterations, taken, &self.simplex) == Control::Stop{
+                break StopReason::ObserverStopped;
+            }
+
+            // with annealing the simplex may climb, progress is measured by the best point so far
+            let current_best = match (&self.annealing, &self.best_ever){
+                (Some(_), Some(best)) => best.value,
+                _ => self.simplex.value(self.simplex.best_index())
+            };
+            if current_best < best_value{
+                best_value = current_best;
+                stalled_iterations = 0;
+            } else {
+                stalled_iterations += 1;
+            }
+            let stalled = self.restart.detect_stalls && stalled_iterations >= self.restart.stall_iterations;
+            let degenerate = (self.iterations - start_iterations).is_multiple_of(self.restart.stall_iterations.max(1))
+                && self.simplex.flatness_with(&mut self.workspace.basis, &mut self.workspace.edge) < cast(self.restart.degeneracy_tolerance);
+            if degenerate || stalled{
+                if self.restarts >= self.restart.max_restarts{
+                    break if degenerate { StopReason::Degenerate } else { StopReason::Stagnated };
+                }
+                self.restart_simplex();
+                stalled_iterations = 0;
+            }
+        };
+        let best = match (&self.annealing, &self.best_ever){
+            (Some(_), Some(best)) => best.clone(),
+            _ => self.simplex.point(self.simplex.best_index())
+        };
+        OptimizationResult{
+            best: best,
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: Some(self.moves),
+            restarts: self.restarts,
+            history: history,
+            stop_reason: stop_reason
         }
     }
 
-    pub fn iterate_until_f_tol(&mut self, f_tol: f64){
-        let mut function_change: f64 = f_tol + 1.0;
-        while function_change > f_tol{
-            let needed_points = self.needed_points();
-            let previous_worst = self.simplex.points[needed_points[2]].value;
-            self.step(&needed_points);
-            function_change = absolute_value(previous_worst - self.simplex.points[needed_points[2]].value);
+}
+
+impl<O: Objective<S::Scalar>, S: Vertices> NelderMeade<O, S>{
+
+    pub fn checkpoint(&self) -> Checkpoint<S::Scalar>{
+        Checkpoint{
+            simplex: Simplex{points: (0..self.simplex.vertex_count()).map(|i| self.simplex.point(i)).collect()},
+            coefficients: self.coefficients,
+            bounds: self.bounds.clone(),
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: self.moves,
+            record_history: self.record_history,
+            restart: self.restart,
+            restarts: self.restarts,
+            initial_step: self.initial_step,
+            vertex_sum: self.workspace.sum.to_vec(),
+            sum_updates: self.workspace.updates,
+            noise: self.noise,
+            samples: self.vertex_samples.clone(),
+            annealing: self.annealing,
+            annealing_rng: self.annealing_rng.clone(),
+            best_ever: self.best_ever.clone()
         }
     }
 
-}
\ No newline at end of file
+    // Picks up exactly where checkpoint() left off. The objective is not part of the
+    // checkpoint and has to be supplied again. Fails if the checkpointed simplex does not fit
+    // the storage S, e.g. a FixedSimplex of another dimension.
+    pub fn from_checkpoint(checkpoint: Checkpoint<S::Scalar>, func: O) -> Result<NelderMeade<O, S>, Error>{
+        let simplex = S::from_simplex(checkpoint.simplex)?;
+        if let Some(bounds) = &checkpoint.bounds{
+            if bounds.dimensions() != simplex.dimensions(){
+                return Err(Error::DimensionMismatch{ expected: simplex.dimensions(), found: bounds.dimensions() });
+            }
+        }
+        let mut workspace = Workspace::new(&simplex);
+        if checkpoint.vertex_sum.len() == simplex.dimensions(){
+            workspace.sum.copy_from_slice(&checkpoint.vertex_sum);
+            workspace.updates = checkpoint.sum_updates;
+        }
+        Ok(NelderMeade{
+            coefficients: checkpoint.coefficients,
+            func: func,
+            bounds: checkpoint.bounds,
+            iterations: checkpoint.iterations,
+            evaluations: checkpoint.evaluations,
+            moves: checkpoint.moves,
+            record_history: checkpoint.record_history,
+            restart: checkpoint.restart,
+            restarts: checkpoint.restarts,
+            initial_step: checkpoint.initial_step,
+            noise: checkpoint.noise,
+            vertex_samples: checkpoint.samples,
+            annealing: checkpoint.annealing,
+            annealing_rng: checkpoint.annealing_rng,
+            best_ever: checkpoint.best_ever,
+            workspace: workspace,
+            simplex: simplex
+        })
+    }
+}
+
+impl<O: Objective<T>, T: Real> NelderMeade<O, Simplex<T>>{
+    // new() on the simplex of Simplex::from_guess around the guess projected onto the bounds,
+    // kept inside them. Takes the same arguments as HookeJeeves::new and the other optimizers
+    // started from a guess.
+    pub fn from_guess(guess: Box<[T]>, step: T, bounds: Option<&Bounds>, mut func: O) -> Result<NelderMeade<O, Simplex<T>>, Error>{
+        let guess = feasible_guess(guess, bounds, &func)?;
+        let simplex = Simplex::from_guess(guess, step, bounds, &mut func)?;
+        let mut nelder_meade = NelderMeade::new(simplex, func);
+        nelder_meade.set_bounds(bounds.cloned())?;
+        Ok(nelder_meade)
+    }
+}
+
+impl<const N: usize, T: Real, F, R> NelderMeade<Fixed<N, F>, FixedSimplex<N, T>> where F: FnMut(&[T; N]) -> R, R: ObjectiveValue<T>{
+    // adaptive() for a fixed-size problem, with an objective that takes arrays. An objective
+    // for another dimension than the simplex is a compile error.
+    pub fn fixed(simplex: FixedSimplex<N, T>, func: F) -> NelderMeade<Fixed<N, F>, FixedSimplex<N, T>>{
+        NelderMeade::adaptive(simplex, Fixed(func))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Every evaluation is worse than the one before, so the best vertex never improves and
+    // every iteration shrinks the simplex, until it is so small that rounding flattens it.
+    fn worsening_search() -> NelderMeade<impl FnMut(&[f64]) -> f64>{
+        let mut count = 0.0;
+        let mut func = move |_: &[f64]| { count += 1.0; count };
+        let simplex = Simplex::from_guess(vec![0.0, 0.0].into_boxed_slice(), 1.0, None, &mut func).unwrap();
+        NelderMeade::new(simplex, func)
+    }
+
+    #[test]
+    fn does_not_stagnate_by_default(){
+        let mut nelder_meade = worsening_search();
+        nelder_meade.restart.stall_iterations = 20;
+        let result = nelder_meade.run(&Termination::new().max_iterations(40));
+        assert_eq!(result.stop_reason, StopReason::MaxIterations);
+    }
+
+    #[test]
+    fn stagnates_with_stall_detection(){
+        let mut nelder_meade = worsening_search();
+        nelder_meade.restart.stall_iterations = 20;
+        nelder_meade.restart.detect_stalls = true;
+        let result = nelder_meade.run(&Termination::new().max_iterations(1_000));
+        assert_eq!(result.stop_reason, StopReason::Stagnated);
+        assert_eq!(result.iterations, nelder_meade.restart.stall_iterations);
+
+        let mut nelder_meade = worsening_search();
+        nelder_meade.restart = RestartPolicy::new(2);
+        nelder_meade.restart.stall_iterations = 20;
+        nelder_meade.restart.detect_stalls = true;
+        let result = nelder_meade.run(&Termination::new().max_iterations(1_000));
+        assert_eq!(result.stop_reason, StopReason::Stagnated);
+        assert_eq!(result.restarts, 2);
+        assert_eq!(result.iterations, 60);
+    }
+
+    #[test]
+    fn checks_for_degeneracy_every_stall_iterations(){
+        let mut nelder_meade = worsening_search();
+        let result = nelder_meade.run(&Termination::new().max_iterations(1_000));
+        assert_eq!(result.stop_reason, StopReason::Degenerate);
+        assert_eq!(result.iterations, nelder_meade.restart.stall_iterations);
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{real::{Real, cast}, error::Error};
+
+// How NelderMeade treats an objective whose values are noisy, such as a Monte Carlo estimate.
+// Set NelderMeade::noise to use it; without it every value is taken at face value, and a single
+// lucky evaluation can hold on to the best vertex until the simplex has collapsed around it.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct NoisePolicy{
+    // Evaluations averaged for every new point while the simplex still has its initial size.
+    pub samples: usize,
+    // The sample count grows as (initial size / diameter)^growth, since vertices closer together
+    // differ by less and need more samples to tell apart. 0 keeps it at samples.
+    pub growth: f64,
+    pub max_samples: usize,
+    // Evaluations added to the best vertex after every iteration, so that its average keeps
+    // improving and an overly optimistic estimate does not last. 0 disables re-evaluation.
+    pub resample_best: usize,
+    // Margin in standard errors of the noise, given to every new point when it is compared with
+    // the simplex: its average minus the margin is compared. Contracting and shrinking, the moves
+    // that noise tends to trigger too early, then only happen when a point is worse beyond doubt.
+    // The noise level is pooled over all points with 2 or more evaluations. 0 compares averages.
+    pub confidence: f64,
+}
+
+impl NoisePolicy{
+    // At least one sample per point, and no more than max_samples.
+    pub fn new(samples: usize, max_samples: usize) -> Result<NoisePolicy, Error>{
+        if samples == 0 || samples > max_samples{
+            return Err(Error::InvalidSampleCounts{ samples: samples, max_samples: max_samples });
+        }
+        Ok(NoisePolicy{
+            samples: samples,
+            max_samples: max_samples,
+            ..NoisePolicy::default()
+        })
+    }
+
+    // Evaluations per new point for a simplex of the given diameter, never more than
+    // max_samples.
+    pub fn samples_for(&self, initial_diameter: f64, diameter: f64) -> usize{
+        let samples = self.samples.max(1);
+        let grown = if self.growth == 0.0 || diameter.is_nan() || diameter <= 0.0 || initial_diameter.is_nan() || initial_diameter <= diameter{
+            samples
+        } else {
+            (samples as f64 * (initial_diameter / diameter).powf(self.growth)).ceil() as usize
+        };
+        grown.max(samples).min(self.max_samples.max(1))
+    }
+}
+
+impl Default for NoisePolicy{
+    fn default() -> NoisePolicy{
+        NoisePolicy{
+            samples: 1,
+            growth: 1.0,
+            max_samples: 64,
+            resample_best: 1,
+            confidence: 1.0
+        }
+    }
+}
+
+// Running average and spread of the evaluations of one point (Welford's algorithm).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(bound = "T: Real")]
+pub struct Samples<T = f64>{
+    pub count: usize,
+    #[serde(with = "crate::serde_float")]
+    pub mean: T,
+    // Sum of squared deviations from the mean.
+    #[serde(with = "crate::serde_float")]
+    pub m2: T,
+}
+
+impl<T: Real> Samples<T>{
+    pub fn new() -> Samples<T>{
+        Samples{count: 0, mean: T::zero(), m2: T::zero()}
+    }
+
+    // A point evaluated once, with the given value.
+    pub fn single(value: T) -> Samples<T>{
+        let mut samples = Samples::new();
+        samples.add(value);
+        samples
+    }
+
+    // A failed evaluation (+inf) rejects the point for good.
+    pub fn add(&mut self, value: T){
+        self.count += 1;
+        if !value.is_finite() || !self.mean.is_finite(){
+            self.mean = T::infinity();
+            return;
+        }
+        let delta = value - self.mean;
+        self.mean += delta / cast(self.count as f64);
+        self.m2 += delta * (value - self.mean);
+    }
+
+    // Adds the evaluations in other, taken at the same point (Chan, Golub & LeVeque).
+    pub fn merge(&mut self, other: &Samples<T>){
+        if other.count == 0{
+            return;
+        }
+        let count = self.count + other.count;
+        if !self.mean.is_finite() || !other.mean.is_finite(){
+            self.count = count;
+            self.mean = T::infinity();
+            return;
+        }
+        let (ours, theirs, total): (T, T, T) = (cast(self.count as f64), cast(other.count as f64), cast(count as f64));
+        let delta = other.mean - self.mean;
+        self.mean += delta * theirs / total;
+        self.m2 += other.m2 + delta * delta * ours * theirs / total;
+        self.count = count;
+    }
+}
+
+impl<T: Real> Default for Samples<T>{
+    fn default() -> Samples<T>{
+        Samples::new()
+    }
+}
+
+// Variance of a single evaluation, pooled over every point with 2 or more finite evaluations.
+// Zero while no point has been evaluated twice.
+pub fn pooled_variance<'a, T: Real, I: Iterator<Item = &'a Samples<T>>>(samples: I) -> T{
+    let mut m2 = T::zero();
+    let mut degrees_of_freedom = 0;
+    for s in samples{
+        if s.count >= 2 && s.mean.is_finite(){
+            m2 += s.m2;
+            degrees_of_freedom += s.count - 1;
+        }
+    }
+    if degrees_of_freedom == 0 { T::zero() } else { m2 / cast(degrees_of_freedom as f64) }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn validates_the_sample_counts(){
+        assert_eq!(NoisePolicy::new(0, 8).err(), Some(Error::InvalidSampleCounts{ samples: 0, max_samples: 8 }));
+        assert_eq!(NoisePolicy::new(9, 8).err(), Some(Error::InvalidSampleCounts{ samples: 9, max_samples: 8 }));
+        assert_eq!(NoisePolicy::new(8, 8).unwrap().samples_for(1.0, 1.0), 8);
+    }
+
+    #[test]
+    fn grows_the_samples_up_to_the_cap(){
+        let policy = NoisePolicy::new(2, 16).unwrap();
+        assert_eq!(policy.samples_for(1.0, 1.0), 2);
+        assert_eq!(policy.samples_for(1.0, 0.25), 8);
+        assert_eq!(policy.samples_for(1.0, 1e-9), 16);
+        assert_eq!(policy.samples_for(1.0, 0.0), 2);
+        assert_eq!(policy.samples_for(1.0, f64::NAN), 2);
+        // fields set by hand beyond the cap still stop at max_samples
+        let policy = NoisePolicy{ samples: 20, ..policy };
+        assert_eq!(policy.samples_for(1.0, 1.0), 16);
+        assert_eq!(policy.samples_for(1.0, 0.1), 16);
+    }
+
+    #[test]
+    fn merged_samples_match_the_combined_samples(){
+        let values: [f64; 5] = [1.0, 4.0, 2.5, -3.0, 7.0];
+        let mut all = Samples::new();
+        values.iter().for_each(|v| all.add(*v));
+        let mut first = Samples::new();
+        let mut second = Samples::new();
+        values[..2].iter().for_each(|v| first.add(*v));
+        values[2..].iter().for_each(|v| second.add(*v));
+        first.merge(&second);
+        assert_eq!(first.count, 5);
+        assert!((first.mean - 2.3).abs() < 1e-12 && (all.mean - 2.3).abs() < 1e-12);
+        assert!((first.m2 - all.m2).abs() < 1e-12);
+        assert!((pooled_variance([all].iter()) - all.m2 / 4.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn a_failed_evaluation_rejects_the_point(){
+        let mut samples = Samples::single(1.0);
+        samples.add(f64::INFINITY);
+        samples.add(0.0);
+        assert_eq!(samples.mean, f64::INFINITY);
+        assert_eq!(pooled_variance([samples].iter()), 0.0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{panic, thread};
+
+use crate::{real::Real, error::Error};
+
+// Anything that maps a point to a value can be minimized. Closures are accepted
+// directly, so objectives are free to capture data (samples, model settings,
+// simulator handles) and to keep state between evaluations. T is the float type of points and
+// values, f64 unless the optimizer works in f32.
+pub trait Objective<T = f64>{
+    fn evaluate(&mut self, x: &[T]) -> T;
+
+    // Used wherever several independent points are needed at once (initial simplex, shrink).
+    // Evaluates them one after another unless overridden, see Parallel and Batched.
+    fn evaluate_batch(&mut self, points: &[&[T]]) -> Vec<T>{
+        points.iter().map(|x| self.evaluate(x)).collect()
+    }
+
+    // Whether evaluate_batch is worth collecting points for. Sequential objectives say no, so
+    // NelderMeade can evaluate shrunk vertices in place without building a batch.
+    fn prefers_batches(&self) -> bool{
+        false
+    }
+
+    // The dimension of the points the objective accepts, if it only accepts one (see Fixed).
+    // Simplex constructors reject a simplex of any other dimension.
+    fn dimensions(&self) -> Option<usize>{
+        None
+    }
+}
+
+// What a closure objective may return. Besides plain values, objectives that can fail may return
+// Option<f64> or Result<f64, E> (f32 likewise); None and Err(_) reject the point instead of
+// aborting the run.
+pub trait ObjectiveValue<T = f64>{
+    fn into_value(self) -> T;
+}
+
+impl<T: Real> ObjectiveValue<T> for T{
+    fn into_value(self) -> T{
+        self
+    }
+}
+
+impl<T: Real> ObjectiveValue<T> for Option<T>{
+    fn into_value(self) -> T{
+        self.unwrap_or(T::infinity())
+    }
+}
+
+impl<T: Real, E> ObjectiveValue<T> for Result<T, E>{
+    fn into_value(self) -> T{
+        self.unwrap_or(T::infinity())
+    }
+}
+
+impl<T: Real, F, R> Objective<T> for F where F: FnMut(&[T]) -> R, R: ObjectiveValue<T>{
+    fn evaluate(&mut self, x: &[T]) -> T{
+        self(x).into_value()
+    }
+}
+
+// Rejected, NaN and infinite evaluations all rank as +inf, i.e. worse than any finite point.
+// The optimizers pass every value they get from an objective through this.
+pub fn sanitize<T: Real>(value: T) -> T{
+    if value.is_finite(){
+        value
+    } else {
+        T::infinity()
+    }
+}
+
+// For objectives written against arrays, typically paired with FixedSimplex. NelderMeade::fixed
+// only accepts an objective whose array length matches the simplex, and the simplex constructors
+// return Error::DimensionMismatch for one that does not. A point of another length is rejected.
+pub struct Fixed<const N: usize, F>(pub F);
+
+impl<T: Real, const N: usize, F, R> Objective<T> for Fixed<N, F> where F: FnMut(&[T; N]) -> R, R: ObjectiveValue<T>{
+    fn evaluate(&mut self, x: &[T]) -> T{
+        match x.try_into(){
+            Ok(x) => (self.0)(x).into_value(),
+            Err(_) => T::infinity()
+        }
+    }
+
+    fn dimensions(&self) -> Option<usize>{
+        Some(N)
+    }
+}
+
+// Error::DimensionMismatch if func only accepts points of another dimension.
+pub fn check_dimensions<T, O: Objective<T> + ?Sized>(func: &O, dimensions: usize) -> Result<(), Error>{
+    match func.dimensions(){
+        Some(expected) if expected != dimensions => Err(Error::DimensionMismatch{ expected: expected, found: dimensions }),
+        _ => Ok(())
+    }
+}
+
+// Opt-in parallel evaluation: batches are split over the available cores with scoped threads.
+// The function only needs shared access, so it has to be Fn + Sync rather than FnMut.
+pub struct Parallel<F>(pub F);
+
+impl<T: Real, F> Objective<T> for Parallel<F> where F: Fn(&[T]) -> T + Sync{
+    fn evaluate(&mut self, x: &[T]) -> T{
+        (self.0)(x)
+    }
+
+    fn evaluate_batch(&mut self, points: &[&[T]]) -> Vec<T>{
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        if threads <= 1 || points.len() <= 1{
+            return points.iter().map(|x| (self.0)(x)).collect();
+        }
+        let chunk_size = points.len().div_ceil(threads);
+        let func = &self.0;
+        thread::scope(|scope| {
+            let handles: Vec<_> = points.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(|x| func(x)).collect::<Vec<T>>()))
+                .collect();
+            let mut values: Vec<T> = Vec::with_capacity(points.len());
+            for handle in handles{
+                match handle.join(){
+                    Ok(chunk_values) => values.extend(chunk_values),
+                    Err(panic) => panic::resume_unwind(panic)
+                }
+            }
+            values
+        })
+    }
+
+    fn prefers_batches(&self) -> bool{
+        true
+    }
+}
+
+// For objectives that are naturally evaluated several points at a time (a simulator that runs
+// a whole population per call, a remote service, ...).
+pub trait BatchObjective<T = f64>{
+    // Must return exactly one value per point, in the same order.
+    fn evaluate_batch(&mut self, points: &[&[T]]) -> Vec<T>;
+}
+
+// Adapts a BatchObjective for the optimizers. Single evaluations are sent as batches of one.
+// A batch that comes back short rejects the points without a value (+inf), extra values are
+// dropped.
+pub struct Batched<B>(pub B);
+
+impl<T: Real, B: BatchObjective<T>> Objective<T> for Batched<B>{
+    fn evaluate(&mut self, x: &[T]) -> T{
+        self.0.evaluate_batch(&[x]).first().copied().map(sanitize).unwrap_or(T::infinity())
+    }
+
+    fn evaluate_batch(&mut self, points: &[&[T]]) -> Vec<T>{
+        let mut values = self.0.evaluate_batch(points);
+        values.resize(points.len(), T::infinity());
+        values
+    }
+
+    fn prefers_batches(&self) -> bool{
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::simplex::{Simplex, FixedSimplex};
+
+    #[test]
+    fn fixed_objectives_reject_other_dimensions(){
+        let mut func = Fixed(|x: &[f64; 2]| x[0] * x[0] + x[1] * x[1]);
+        assert_eq!(func.evaluate(&[1.0, 2.0]), 5.0);
+        assert_eq!(func.evaluate(&[1.0, 2.0, 3.0]), f64::INFINITY);
+
+        let simplex = Simplex::from_guess(vec![1.0, 2.0, 3.0].into_boxed_slice(), 1.0, None, &mut func);
+        assert_eq!(simplex.err(), Some(Error::DimensionMismatch{ expected: 2, found: 3 }));
+        let simplex = FixedSimplex::from_guess([1.0, 2.0, 3.0], 1.0, None, &mut func);
+        assert_eq!(simplex.err(), Some(Error::DimensionMismatch{ expected: 2, found: 3 }));
+        assert!(Simplex::from_guess(vec![1.0, 2.0].into_boxed_slice(), 1.0, None, &mut func).is_ok());
+    }
+
+    // Returns one value fewer than asked for.
+    struct Short;
+
+    impl BatchObjective for Short{
+        fn evaluate_batch(&mut self, points: &[&[f64]]) -> Vec<f64>{
+            points.iter().skip(1).map(|x| x[0]).collect()
+        }
+    }
+
+    // Returns one value more than asked for.
+    struct Long;
+
+    impl BatchObjective for Long{
+        fn evaluate_batch(&mut self, points: &[&[f64]]) -> Vec<f64>{
+            points.iter().map(|x| x[0]).chain([f64::NAN]).collect()
+        }
+    }
+
+    #[test]
+    fn batches_of_the_wrong_length_give_one_value_per_point(){
+        let points: [&[f64]; 3] = [&[1.0], &[2.0], &[3.0]];
+        assert_eq!(Batched(Short).evaluate(&[1.0]), f64::INFINITY);
+        assert_eq!(Batched(Short).evaluate_batch(&points), vec![2.0, 3.0, f64::INFINITY]);
+        assert_eq!(Batched(Long).evaluate(&[1.0]), 1.0);
+        assert_eq!(Batched(Long).evaluate_batch(&points), vec![1.0, 2.0, 3.0]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+
+use crate::{nelder_meade::Move, simplex::Simplex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control{
+    Continue,
+    Stop,
+}
+
+// Called by NelderMeade::run_observed after every step with the iteration number, the move
+// that was taken and the simplex after the move. S is the simplex type of the optimizer.
+pub trait Observer<S: ?Sized = Simplex>{
+    fn observe(&mut self, iteration: usize, taken: Move, simplex: &S) -> Control;
+}
+
+impl<F, S: ?Sized> Observer<S> for F where F: FnMut(usize, Move, &S) -> Control{
+    fn observe(&mut self, iteration: usize, taken: Move, simplex: &S) -> Control{
+        self(iteration, taken, simplex)
+    }
+}
+
+// Shared flag for stopping a run from another thread. Clones share the same flag; hand one to
+// Termination::cancellation and keep the other.
+#[derive(Clone, Default)]
+pub struct CancellationToken{
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken{
+    pub fn new() -> CancellationToken{
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self){
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool{
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{nelder_meade::NelderMeade, termination::{Termination, StopReason}};
+
+    fn search() -> NelderMeade<fn(&[f64]) -> f64>{
+        let mut func: fn(&[f64]) -> f64 = |x| x[0] * x[0] + x[1] * x[1];
+        let simplex = Simplex::from_guess(vec![3.0, 4.0].into_boxed_slice(), 1.0, None, &mut func).unwrap();
+        NelderMeade::new(simplex, func)
+    }
+
+    #[test]
+    fn observer_sees_every_step_and_can_stop(){
+        let mut seen = Vec::new();
+        let mut observer = |iteration: usize, _: Move, _: &Simplex| {
+            seen.push(iteration);
+            if iteration == 7 { Control::Stop } else { Control::Continue }
+        };
+        let result = search().run_observed(&Termination::new().max_iterations(100), &mut observer);
+        assert_eq!(result.stop_reason, StopReason::ObserverStopped);
+        assert_eq!(seen, (1..=7).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn cancelled_runs_stop(){
+        let token = CancellationToken::new();
+        let criteria = Termination::new().max_iterations(100).cancellation(token.clone());
+        let mut nelder_meade = search();
+        let mut observer = |iteration: usize, _: Move, _: &Simplex| {
+            if iteration == 3{
+                token.cancel();
+            }
+            Control::Continue
+        };
+        let result = nelder_meade.run_observed(&criteria, &mut observer);
+        assert_eq!(result.stop_reason, StopReason::Cancelled);
+        assert_eq!(result.iterations, 3);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{nelder_meade::NelderMeade, simplex::{Vertices, check_guess}, objective::{Objective, sanitize, check_dimensions}, real::Real, bounds::Bounds, error::Error, termination::Termination, result::OptimizationResult};
+
+// What every local optimizer in this crate offers, so that one can be swapped for another
+// without touching the objective, the stopping criteria or the handling of the result:
+//
+//     let mut optimizer = HookeJeeves::new(guess, 0.5, bounds.as_ref(), func)?;
+//     let result = optimizer.run(&criteria);
+//
+// HookeJeeves, Powell, CmaEs and Subplex are built by new(guess, step, bounds, func), and
+// NelderMeade and MultiDirectional by from_guess with the same arguments. All of them start
+// from the guess projected onto the bounds and keep every point inside them.
+//
+// Termination::f_tol and Termination::x_tol are compared with
+//     NelderMeade, MultiDirectional: spread of the vertex values, simplex diameter
+//     HookeJeeves: spread of the values around the base point in the last exploration, step size
+//     Powell: decrease of the best value in the last iteration, length of the last iteration's move
+//     CmaEs: spread of the values in the last generation, largest standard deviation (converging
+//         starts a restart while restarts are left)
+//     Subplex: largest spread of the vertex values of a subspace simplex in the last iteration,
+//         largest of the last move and the scaled step sizes along any coordinate
+pub trait Optimizer{
+    type Scalar: Real;
+
+    // Iterates until one of the criteria is met. Counters in the result cover the whole
+    // lifetime of the optimizer, not just this run.
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<Self::Scalar>;
+}
+
+impl<O: Objective<S::Scalar>, S: Vertices> Optimizer for NelderMeade<O, S>{
+    type Scalar = S::Scalar;
+
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<S::Scalar>{
+        NelderMeade::run(self, criteria)
+    }
+}
+
+// The starting point of an optimizer built from a guess: checked like any guess (see
+// check_guess) and against the dimension of the objective, then projected onto the bounds.
+pub fn feasible_guess<T: Real, O: Objective<T> + ?Sized>(guess: Box<[T]>, bounds: Option<&Bounds>, func: &O) -> Result<Box<[T]>, Error>{
+    check_guess(&guess, bounds)?;
+    check_dimensions(func, guess.len())?;
+    Ok(match bounds{
+        Some(bounds) => bounds.project(guess)?,
+        None => guess
+    })
+}
+
+// Evaluates all points, as one batch if the objective prefers batches. Failed and non-finite
+// evaluations come back as +inf, and so do the missing values of a short batch.
+pub fn evaluate_points<T: Real, O: Objective<T> + ?Sized>(func: &mut O, evaluations: &mut usize, points: &[&[T]]) -> Vec<T>{
+    *evaluations += points.len();
+    if func.prefers_batches(){
+        let mut values = func.evaluate_batch(points).into_iter();
+        points.iter().map(|_| values.next().map_or(T::infinity(), sanitize)).collect()
+    } else {
+        points.iter().map(|x| sanitize(func.evaluate(x))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{hooke_jeeves::HookeJeeves, powell::Powell, cma_es::CmaEs, subplex::Subplex, multidirectional::MultiDirectional, objective::Fixed};
+
+    // Smallest at (1, 1, 1).
+    fn shifted_sphere(x: &[f64]) -> f64{
+        x.iter().map(|xi| (xi - 1.0) * (xi - 1.0)).sum()
+    }
+
+    fn run_inside<O: Optimizer<Scalar = f64>>(name: &str, mut optimizer: O, bounds: &Bounds){
+        let result = optimizer.run(&Termination::new().max_evaluations(20_000).x_tol(1e-10));
+        // only the simplex searches take Nelder-Mead moves
+        let simplex_search = ["NelderMeade", "MultiDirectional", "Subplex"].contains(&name);
+        assert_eq!(result.moves.is_some(), simplex_search, "{}", name);
+        assert!(bounds.contains(&result.best.x), "{}: {:?}", name, result.best.x);
+        assert!(result.best.x.iter().all(|x| (x - 1.0).abs() < 1e-6), "{}: {:?} {}", name, result.best.x, result.stop_reason);
+    }
+
+    #[test]
+    fn every_optimizer_starts_from_the_same_arguments(){
+        // the guess lies outside the box
+        let bounds = Bounds::new(vec![-2.0; 3], vec![2.0; 3]).unwrap();
+        let guess = || vec![3.0, -5.0, 0.0].into_boxed_slice();
+        run_inside("NelderMeade", NelderMeade::from_guess(guess(), 0.5, Some(&bounds), shifted_sphere).unwrap(), &bounds);
+        run_inside("MultiDirectional", MultiDirectional::from_guess(guess(), 0.5, Some(&bounds), shifted_sphere).unwrap(), &bounds);
+        run_inside("HookeJeeves", HookeJeeves::new(guess(), 0.5, Some(&bounds), shifted_sphere).unwrap(), &bounds);
+        run_inside("Powell", Powell::new(guess(), 0.5, Some(&bounds), shifted_sphere).unwrap(), &bounds);
+        run_inside("CmaEs", CmaEs::new(guess(), 0.5, Some(&bounds), shifted_sphere).unwrap(), &bounds);
+        run_inside("Subplex", Subplex::new(guess(), 0.5, Some(&bounds), shifted_sphere).unwrap(), &bounds);
+    }
+
+    #[test]
+    fn checks_the_guess_against_bounds_and_objective(){
+        let bounds = Bounds::new(vec![-2.0; 2], vec![0.5; 2]).unwrap();
+        let mismatch = Some(Error::DimensionMismatch{ expected: 3, found: 2 });
+        assert_eq!(HookeJeeves::new(vec![0.0; 3].into_boxed_slice(), 0.5, Some(&bounds), shifted_sphere).err(), mismatch);
+        assert_eq!(Powell::new(vec![0.0; 3].into_boxed_slice(), 0.5, Some(&bounds), shifted_sphere).err(), mismatch);
+        assert_eq!(Subplex::new(vec![0.0; 3].into_boxed_slice(), 0.5, Some(&bounds), shifted_sphere).err(), mismatch);
+        assert_eq!(NelderMeade::from_guess(vec![0.0; 3].into_boxed_slice(), 0.5, Some(&bounds), shifted_sphere).err(), mismatch);
+
+        let fixed = Fixed(|x: &[f64; 2]| x[0] + x[1]);
+        assert_eq!(CmaEs::new(vec![0.0; 3].into_boxed_slice(), 0.5, None, fixed).err(), Some(Error::DimensionMismatch{ expected: 2, found: 3 }));
+    }
+}
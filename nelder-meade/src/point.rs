@@ -1,12 +1,31 @@
 use std::fmt;
 
-#[derive(Clone)]
-pub struct Point{
-    pub x: Box<[f64]>,
-    pub value: f64,
+use serde::{Serialize, Deserialize};
+
+use crate::real::Real;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Real")]
+pub struct Point<T = f64>{
+    pub x: Box<[T]>,
+    #[serde(with = "crate::serde_float")]
+    pub value: T,
+}
+
+// Stack-allocated point of a dimension known at compile time, used by FixedSimplex.
+#[derive(Clone, Copy)]
+pub struct FixedPoint<const N: usize, T = f64>{
+    pub x: [T; N],
+    pub value: T,
+}
+
+impl<const N: usize, T: Real> From<FixedPoint<N, T>> for Point<T>{
+    fn from(point: FixedPoint<N, T>) -> Point<T>{
+        Point{x: Box::new(point.x), value: point.value}
+    }
 }
 
-impl fmt::Display for Point {
+impl<T: Real> fmt::Display for Point<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
         let mut representation: String = String::new();
         representation.push_str("Coordinates: ");
@@ -0,0 +1,314 @@
+use std::time::Instant;
+
+use crate::{point::Point, objective::{Objective, sanitize}, real::{Real, cast}, bounds::Bounds, error::Error, optimizer::{Optimizer, feasible_guess}, termination::{Termination, StopReason}, result::{OptimizationResult, IterationRecord}};
+
+// Powell's conjugate direction method (1964). Every iteration minimizes along each of n search
+// directions in turn, then replaces the direction of the largest decrease by the overall move
+// of the iteration, which on a quadratic builds up a set of conjugate directions. The line
+// minimizations use Brent's method. With bounds, the points along a line are projected onto
+// the box.
+pub struct Powell<O, T = f64> where O: Objective<T>{
+    pub func: O,
+    // First trial step of every line search, used to bracket the minimum.
+    pub step: T,
+    // Relative accuracy of the line minimizations. Defaults to the square root of the machine
+    // epsilon of T, about as accurate as a minimum can be located from function values.
+    pub line_tolerance: f64,
+    pub iterations: usize,
+    pub evaluations: usize,
+    // Keep a per-iteration record of the best value and length of the move in run().
+    pub record_history: bool,
+    // The guess was projected onto them, see new.
+    bounds: Option<Bounds>,
+    best: Point<T>,
+    // Unit vectors, the coordinate axes to begin with.
+    directions: Vec<Box<[T]>>,
+    // Of the last iteration, for Termination::f_tol and Termination::x_tol.
+    decrease: T,
+    moved: T,
+}
+
+const GOLDEN_SECTION: f64 = 0.381_966_011_250_105;
+const GOLDEN_RATIO: f64 = 1.618_033_988_749_895;
+// Bracketing gives up after this many steps downhill and takes the lowest point found.
+const MAX_BRACKET_STEPS: usize = 50;
+const MAX_LINE_ITERATIONS: usize = 100;
+
+impl<O: Objective<T>, T: Real> Powell<O, T>{
+
+    // Starts from the guess projected onto the bounds, if any.
+    pub fn new(guess: Box<[T]>, step: T, bounds: Option<&Bounds>, mut func: O) -> Result<Powell<O, T>, Error>{
+        let guess = feasible_guess(guess, bounds, &func)?;
+        if !step.is_finite() || step <= T::zero(){
+            return Err(Error::InvalidStep);
+        }
+        let dimensions = guess.len();
+        let value = sanitize(func.evaluate(&guess));
+        Ok(Powell{
+            func: func,
+            bounds: bounds.cloned(),
+            step: step,
+            line_tolerance: T::epsilon().sqrt().to_f64().unwrap(),
+            iterations: 0,
+            evaluations: 1,
+            record_history: false,
+            best: Point{x: guess, value: value},
+            directions: (0..dimensions).map(|i| (0..dimensions).map(|k| if k == i { T::one() } else { T::zero() }).collect()).collect(),
+            decrease: T::infinity(),
+            moved: T::infinity()
+        })
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.bounds.as_ref()
+    }
+
+    pub fn best(&self) -> &Point<T>{
+        &self.best
+    }
+
+    // best + t * direction, projected onto the bounds.
+    fn along(&self, direction: &[T], t: T) -> Box<[T]>{
+        let mut x: Box<[T]> = self.best.x.iter().zip(direction).map(|(x, d)| *x + t * *d).collect();
+        if let Some(bounds) = &self.bounds{
+            bounds.clamp(&mut x);
+        }
+        x
+    }
+
+    fn value_along(&mut self, direction: &[T], t: T) -> T{
+        let x = self.along(direction, t);
+        self.evaluations += 1;
+        sanitize(self.func.evaluate(&x))
+    }
+
+    // Brackets a minimum along direction and refines it with Brent's method. Returns the
+    // parameter and value of the lowest point found, which is never worse than t = 0.
+    fn line_minimum(&mut self, direction: &[T]) -> (T, T){
+        let mut a = T::zero();
+        let fa = self.best.value;
+        let (mut b, mut fb) = (self.step, self.value_along(direction, self.step));
+        // no better also when b was projected back onto a, so that a line search starting
+        // against a bound tries the other way
+        if fb >= fa{
+            let (c, fc) = (-self.step, self.value_along(direction, -self.step));
+            if fc > fa{
+                return self.brent(direction, c, b, a, fa);
+            }
+            (b, fb) = (c, fc);
+        }
+        // downhill from a to b: keep going until the values rise again
+        for _ in 0..MAX_BRACKET_STEPS{
+            let c = b + cast::<T>(GOLDEN_RATIO) * (b - a);
+            let fc = self.value_along(direction, c);
+            if fc > fb{
+                return self.brent(direction, a.min(c), a.max(c), b, fb);
+            }
+            a = b;
+            (b, fb) = (c, fc);
+        }
+        (b, fb)
+    }
+
+    // Brent's minimization on [lower, upper], starting from the interior point x with value fx.
+    fn brent(&mut self, direction: &[T], mut lower: T, mut upper: T, mut x: T, mut fx: T) -> (T, T){
+        let golden: T = cast(GOLDEN_SECTION);
+        let half: T = cast(0.5);
+        let two: T = cast(2.0);
+        let tolerance: T = cast(self.line_tolerance);
+        // keeps the tolerance from vanishing when the minimum is at t = 0
+        let floor: T = tolerance * self.step;
+        let (mut w, mut fw, mut v, mut fv) = (x, fx, x, fx);
+        // the last two steps
+        let mut d = T::zero();
+        let mut e = T::zero();
+        for _ in 0..MAX_LINE_ITERATIONS{
+            let middle = half * (lower + upper);
+            let tol1 = tolerance * x.abs() + floor;
+            let tol2 = two * tol1;
+            if (x - middle).abs() <= tol2 - half * (upper - lower){
+                break;
+            }
+            let mut golden_step = true;
+            if e.abs() > tol1{
+                // parabola through x, w and v
+                let r = (x - w) * (fx - fv);
+                let mut q = (x - v) * (fx - fw);
+                let mut p = (x - v) * q - (x - w) * r;
+                q = two * (q - r);
+                if q > T::zero(){
+                    p = -p;
+                }
+                q = q.abs();
+                let previous = e;
+                e = d;
+                if p.abs() < (half * q * previous).abs() && p > q * (lower - x) && p < q * (upper - x){
+                    d = p / q;
+                    let u = x + d;
+                    if u - lower < tol2 || upper - u < tol2{
+                        d = tol1.copysign(middle - x);
+                    }
+                    golden_step = false;
+                }
+            }
+            if golden_step{
+                e = if x >= middle { lower - x } else { upper - x };
+                d = golden * e;
+            }
+            let u = if d.abs() >= tol1 { x + d } else { x + tol1.copysign(d) };
+            let fu = self.value_along(direction, u);
+            if fu <= fx{
+                if u >= x { lower = x } else { upper = x }
+                (v, fv) = (w, fw);
+                (w, fw) = (x, fx);
+                (x, fx) = (u, fu);
+            } else {
+                if u < x { lower = u } else { upper = u }
+                if fu <= fw || w == x{
+                    (v, fv) = (w, fw);
+                    (w, fw) = (u, fu);
+                } else if fu <= fv || v == x || v == w{
+                    (v, fv) = (u, fu);
+                }
+            }
+        }
+        (x, fx)
+    }
+
+    // Moves the best point to the minimum along direction.
+    fn line_search(&mut self, direction: &[T]){
+        let (t, value) = self.line_minimum(direction);
+        if value < self.best.value{
+            self.best.x = self.along(direction, t);
+            self.best.value = value;
+        }
+    }
+
+    pub fn step(&mut self){
+        let start = self.best.clone();
+        let mut largest_decrease = T::zero();
+        let mut largest_index = 0;
+        for i in 0..self.directions.len(){
+            let before = self.best.value;
+            let direction = self.directions[i].clone();
+            self.line_search(&direction);
+            if before - self.best.value > largest_decrease{
+                largest_decrease = before - self.best.value;
+                largest_index = i;
+            }
+        }
+
+        let mut moved: Box<[T]> = self.best.x.iter().zip(start.x.iter()).map(|(x, s)| *x - *s).collect();
+        let length = moved.iter().fold(T::zero(), |sum, m| sum + *m * *m).sqrt();
+        self.decrease = start.value - self.best.value;
+        self.moved = length;
+        if length == T::zero(){
+            return;
+        }
+
+        // Powell's test whether the move of the iteration is worth a direction of its own.
+        let mut extrapolated: Box<[T]> = self.best.x.iter().zip(moved.iter()).map(|(x, m)| *x + *m).collect();
+        if let Some(bounds) = &self.bounds{
+            bounds.clamp(&mut extrapolated);
+        }
+        self.evaluations += 1;
+        let extrapolated_value = sanitize(self.func.evaluate(&extrapolated));
+        let (f0, f1, fe) = (start.value, self.best.value, extrapolated_value);
+        if fe < f0{
+            let two: T = cast(2.0);
+            let test = two * (f0 - two * f1 + fe) * (f0 - f1 - largest_decrease).powi(2) - largest_decrease * (f0 - fe).powi(2);
+            if test < T::zero(){
+                for m in moved.iter_mut(){
+                    *m /= length;
+                }
+                self.line_search(&moved);
+                let last = self.directions.len() - 1;
+                self.directions.swap(largest_index, last);
+                self.directions[last] = moved;
+            }
+        }
+    }
+
+    pub fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+        let start_evaluations = self.evaluations;
+        let mut history: Option<Vec<IterationRecord<T>>> = if self.record_history { Some(Vec::new()) } else { None };
+        let stop_reason: StopReason = loop {
+            if let Some(reason) = criteria.check_progress(self.decrease, self.moved, self.iterations - start_iterations, self.evaluations - start_evaluations, start){
+                break reason;
+            }
+            self.step();
+            self.iterations += 1;
+            if let Some(history) = &mut history{
+                history.push(IterationRecord{
+                    iteration: self.iterations,
+                    best_value: self.best.value,
+                    diameter: self.moved
+                });
+            }
+        };
+        OptimizationResult{
+            best: self.best.clone(),
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: None,
+            restarts: 0,
+            history: history,
+            stop_reason: stop_reason
+        }
+    }
+}
+
+impl<O: Objective<T>, T: Real> Optimizer for Powell<O, T>{
+    type Scalar = T;
+
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        Powell::run(self, criteria)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn rosenbrock(x: &[f64]) -> f64{
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)
+    }
+
+    // Smallest at x0 = 0.3, not a parabola along x0.
+    fn quartic(x: &[f64]) -> f64{
+        let d = x[0] - 0.3;
+        d * d + d.powi(4) + x[1] * x[1]
+    }
+
+    #[test]
+    fn brent_locates_the_line_minimum(){
+        let mut powell = Powell::new(vec![0.0, 0.0].into_boxed_slice(), 1.0, None, quartic).unwrap();
+        let (t, value) = powell.line_minimum(&[1.0, 0.0]);
+        assert!((t - 0.3).abs() < 1e-7, "{}", t);
+        assert!(value <= quartic(&[0.3, 0.0]) + 1e-14);
+        // the minimum lies behind the first trial step
+        let (t, _) = powell.line_minimum(&[-1.0, 0.0]);
+        assert!((t + 0.3).abs() < 1e-7, "{}", t);
+        // no decrease along x1, so the line search stays put
+        let (t, value) = powell.line_minimum(&[0.0, 1.0]);
+        assert!(t.abs() < 1e-7 && value <= powell.best().value);
+    }
+
+    #[test]
+    fn minimizes_rosenbrock(){
+        let mut powell = Powell::new(vec![-1.2, 1.0].into_boxed_slice(), 0.5, None, rosenbrock).unwrap();
+        let result = powell.run(&Termination::new().max_iterations(1_000).x_tol(1e-12));
+        assert_eq!(result.stop_reason, StopReason::StepTolerance);
+        assert!((result.best.x[0] - 1.0).abs() < 1e-5 && (result.best.x[1] - 1.0).abs() < 1e-5, "{:?}", result.best.x);
+    }
+
+    #[test]
+    fn rejects_invalid_steps(){
+        for step in [0.0, -1.0, f64::NAN]{
+            assert_eq!(Powell::new(vec![0.0, 0.0].into_boxed_slice(), step, None, rosenbrock).err(), Some(Error::InvalidStep));
+        }
+        assert_eq!(Powell::new(vec![0.0].into_boxed_slice(), 1.0, None, rosenbrock).err(), Some(Error::TooFewDimensions{ found: 1 }));
+    }
+}
@@ -0,0 +1,18 @@
+use std::fmt::{Debug, Display};
+
+use num_traits::{Float, NumAssign};
+use serde::{Serialize, de::DeserializeOwned};
+
+// The floating-point types points, simplices and the optimizer can work in: f64, or f32 for
+// objectives that compute in single precision (GPU code, for instance) and should not have
+// their values converted back and forth.
+pub trait Real: Float + NumAssign + Debug + Display + Send + Sync + Serialize + DeserializeOwned + 'static {}
+
+impl<T> Real for T where T: Float + NumAssign + Debug + Display + Send + Sync + Serialize + DeserializeOwned + 'static {}
+
+// Settings such as coefficients, bounds and tolerances stay f64 and are converted where they
+// meet coordinates or values.
+pub fn cast<T: Real>(value: f64) -> T{
+    // float to float conversions round, they cannot fail
+    T::from(value).unwrap()
+}
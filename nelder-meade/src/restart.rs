@@ -0,0 +1,44 @@
+use serde::{Serialize, Deserialize};
+
+use crate::simplex::DEGENERACY_TOLERANCE;
+
+// When NelderMeade::run rebuilds the simplex around the best vertex.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct RestartPolicy{
+    // 0 disables restarts; the run then stops with StopReason::Degenerate or, with detect_stalls,
+    // StopReason::Stagnated.
+    pub max_restarts: usize,
+    // Iterations between two degeneracy checks, which take O(n^3) operations each. With
+    // detect_stalls also the iterations without any improvement of the best value after which
+    // the simplex is rebuilt or the run stops.
+    pub stall_iterations: usize,
+    // Off by default, so that a run only stops on its Termination criteria or a degenerate
+    // simplex. Turn it on to restart (or stop) searches that still move but no longer improve.
+    #[serde(default)]
+    pub detect_stalls: bool,
+    // See Simplex::flatness.
+    pub degeneracy_tolerance: f64,
+    // Edge length of the rebuilt simplex. None reuses the diameter of the initial simplex.
+    pub step: Option<f64>,
+}
+
+impl RestartPolicy{
+    pub fn new(max_restarts: usize) -> RestartPolicy{
+        RestartPolicy{
+            max_restarts: max_restarts,
+            ..RestartPolicy::default()
+        }
+    }
+}
+
+impl Default for RestartPolicy{
+    fn default() -> RestartPolicy{
+        RestartPolicy{
+            max_restarts: 0,
+            stall_iterations: 200,
+            detect_stalls: false,
+            degeneracy_tolerance: DEGENERACY_TOLERANCE,
+            step: None
+        }
+    }
+}
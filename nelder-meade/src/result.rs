@@ -0,0 +1,76 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{point::Point, nelder_meade::Move, termination::StopReason, real::Real};
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveCounts{
+    pub reflect: usize,
+    pub expand: usize,
+    pub outside_contract: usize,
+    pub inside_contract: usize,
+    pub shrink: usize,
+}
+
+impl MoveCounts{
+    pub fn record(&mut self, taken: Move){
+        match taken{
+            Move::Reflect => self.reflect += 1,
+            Move::Expand => self.expand += 1,
+            Move::OutsideContract => self.outside_contract += 1,
+            Move::InsideContract => self.inside_contract += 1,
+            Move::Shrink => self.shrink += 1
+        }
+    }
+
+    pub fn add(&mut self, other: &MoveCounts){
+        self.reflect += other.reflect;
+        self.expand += other.expand;
+        self.outside_contract += other.outside_contract;
+        self.inside_contract += other.inside_contract;
+        self.shrink += other.shrink;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(bound = "T: Real")]
+pub struct IterationRecord<T = f64>{
+    pub iteration: usize,
+    #[serde(with = "crate::serde_float")]
+    pub best_value: T,
+    #[serde(with = "crate::serde_float")]
+    pub diameter: T,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Real")]
+pub struct OptimizationResult<T = f64>{
+    pub best: Point<T>,
+    pub iterations: usize,
+    pub evaluations: usize,
+    // The Nelder-Mead moves taken. None for the optimizers that take none (HookeJeeves, Powell,
+    // CmaEs).
+    pub moves: Option<MoveCounts>,
+    pub restarts: usize,
+    // Only filled in when history recording was switched on.
+    pub history: Option<Vec<IterationRecord<T>>>,
+    pub stop_reason: StopReason,
+}
+
+impl<T: Real> fmt::Display for OptimizationResult<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        let mut representation: String = String::new();
+        representation.push_str(&format!("Stopped: {}\n", self.stop_reason));
+        representation.push_str(&format!("Iterations: {}\n", self.iterations));
+        representation.push_str(&format!("Function evaluations: {}\n", self.evaluations));
+        if let Some(moves) = &self.moves{
+            representation.push_str(&format!("Moves: reflect {} expand {} outside contract {} inside contract {} shrink {}\n",
+                moves.reflect, moves.expand, moves.outside_contract, moves.inside_contract, moves.shrink));
+        }
+        representation.push_str(&format!("Restarts: {}\n", self.restarts));
+        representation.push_str("Best point:\n");
+        representation.push_str(&self.best.to_string());
+        write!(f, "{}", representation)
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+
+// Small seeded generator (SplitMix64). Good enough for sampling start points and perturbations,
+// and fully reproducible from the seed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rng{
+    pub state: u64,
+}
+
+impl Rng{
+    pub fn new(seed: u64) -> Rng{
+        Rng{state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64{
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64{
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Standard normal (Box-Muller).
+    pub fn normal(&mut self) -> f64{
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+
+    // Uniform in 0..n.
+    pub fn below(&mut self, n: usize) -> usize{
+        (self.next_f64() * n as f64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]){
+        for i in (1..items.len()).rev(){
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use serde::{Serializer, Deserializer, Serialize, Deserialize, de::{self, Visitor}};
+
+use crate::real::{Real, cast};
+
+// JSON has no infinities or NaN: serde_json writes them as null and then fails to read them back.
+// Unbounded coordinates and rejected points are infinite, so fields that can hold them use
+// #[serde(with = "crate::serde_float")] (or serde_float::slice). Human-readable formats get the
+// strings "inf", "-inf" and "NaN" for non-finite values; binary formats get the plain number.
+pub struct Float<T = f64>(pub T);
+
+impl<T: Real> Serialize for Float<T>{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        if self.0.is_finite() || !serializer.is_human_readable(){
+            self.0.serialize(serializer)
+        } else if self.0.is_nan(){
+            serializer.serialize_str("NaN")
+        } else if self.0 > T::zero(){
+            serializer.serialize_str("inf")
+        } else {
+            serializer.serialize_str("-inf")
+        }
+    }
+}
+
+struct FloatVisitor;
+
+impl<'de> Visitor<'de> for FloatVisitor{
+    type Value = f64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "a number or one of \"inf\", \"-inf\", \"NaN\"")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E>{
+        Ok(value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E>{
+        Ok(value as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E>{
+        Ok(value as f64)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E>{
+        match value{
+            "inf" => Ok(f64::INFINITY),
+            "-inf" => Ok(f64::NEG_INFINITY),
+            "NaN" => Ok(f64::NAN),
+            _ => Err(E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+}
+
+impl<'de, T: Real> Deserialize<'de> for Float<T>{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Float<T>, D::Error>{
+        if deserializer.is_human_readable(){
+            deserializer.deserialize_any(FloatVisitor).map(|value| Float(cast(value)))
+        } else {
+            T::deserialize(deserializer).map(Float)
+        }
+    }
+}
+
+pub fn serialize<T: Real, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error>{
+    Float(*value).serialize(serializer)
+}
+
+pub fn deserialize<'de, T: Real, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error>{
+    Float::deserialize(deserializer).map(|f| f.0)
+}
+
+pub mod slice{
+    use serde::{Serializer, Deserializer, Deserialize};
+
+    use super::Float;
+    use crate::real::Real;
+
+    pub fn serialize<T: Real, S: Serializer>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_seq(values.iter().map(|v| Float(*v)))
+    }
+
+    pub fn deserialize<'de, T: Real, D: Deserializer<'de>>(deserializer: D) -> Result<Box<[T]>, D::Error>{
+        let values: Vec<Float<T>> = Vec::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|f| f.0).collect())
+    }
+}
@@ -1,85 +1,474 @@
-use std::fmt;
-
-use crate::{point::Point, helper_functions::{add_to_index, add_to_slice, multiply_by_const}};
-
-pub struct Simplex{
-    pub points: Box<[Point]>
-}
-
-impl Simplex{
-    //needs to add colinearaity cond!
-    pub fn new(mut points: Vec<Vec<f64>>, func: fn(&[f64]) -> f64) -> Result<Simplex, String>{
-
-        let num_point = points.len();
-        
-        let mut points_vector:Vec<Point> = Vec::with_capacity(num_point);
-        
-        if num_point <= 2{
-            return Err("Point vector is too short! Must contain n+1 points of exactly dimension n. n must be at least 2".to_owned());
-        }
-        
-        while !points.is_empty() {
-            let point_vector = match points.pop(){
-                None => return Err("Pop from point_vector failed".to_owned()),
-                Some(p) => p
-            };
-            if point_vector.len() + 1 != num_point{
-                return Err(format!("Length mismatch in positon {}. Either this vector is the wrong 
-                    length ({}) or there are too many or too few 
-                    simplex vectors {}", points.len(), point_vector.len(), num_point));
-            } else {
-                let value = func(&point_vector);
-                points_vector.push(
-                    Point{
-                    x: point_vector.into_boxed_slice(), 
-                    value: value
-                });
+use std::{fmt, cmp::Ordering};
+
+use num_traits::{Float, Zero};
+use serde::{Serialize, Deserialize};
+
+use crate::{point::{Point, FixedPoint}, helper_functions::{add_to_index, add_assign}, objective::{Objective, sanitize, check_dimensions}, bounds::Bounds, real::{Real, cast}, error::Error, initial::InitialSimplex};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Real")]
+pub struct Simplex<T = f64>{
+    pub points: Box<[Point<T>]>
+}
+
+// Simplices flatter than this are rejected at construction and trigger a restart while iterating.
+pub const DEGENERACY_TOLERANCE: f64 = 1e-10;
+
+// Gram-Schmidt over the edges x_i - x_0. For every edge this measures the part that is orthogonal
+// to the edges before it, relative to the length of the edge, and returns the smallest such ratio:
+// 1.0 for a right-angled simplex, 0.0 when the vertices are colinear/coplanar or coincide.
+fn flatness<T: Real>(vertices: &[&[T]]) -> T{
+    let dimensions = vertices[0].len();
+    let mut basis: Vec<T> = vec![T::zero(); dimensions * dimensions];
+    let mut edge: Vec<T> = vec![T::zero(); dimensions];
+    flatness_in(vertices.len(), |i| vertices[i], &mut basis, &mut edge)
+}
+
+// flatness() on caller-provided scratch space: basis holds dimensions^2 values, edge dimensions.
+fn flatness_in<'a, T: Real, V: Fn(usize) -> &'a [T]>(count: usize, vertex: V, basis: &mut [T], edge: &mut [T]) -> T{
+    let dimensions = edge.len();
+    let origin = vertex(0);
+    let mut smallest_ratio: T = T::one();
+    for i in 1..count{
+        let current = vertex(i);
+        for k in 0..dimensions{
+            edge[k] = current[k] - origin[k];
+        }
+        let length = edge.iter().fold(T::zero(), |sum, e| sum + *e * *e).sqrt();
+        if length == T::zero(){
+            return T::zero();
+        }
+        for q in basis[..(i - 1) * dimensions].chunks(dimensions){
+            let projection: T = edge.iter().zip(q.iter()).fold(T::zero(), |sum, (e, q)| sum + *e * *q);
+            for k in 0..dimensions{
+                edge[k] -= projection * q[k];
             }
         }
+        let residual = edge.iter().fold(T::zero(), |sum, e| sum + *e * *e).sqrt();
+        smallest_ratio = smallest_ratio.min(residual / length);
+        if residual == T::zero(){
+            return T::zero();
+        }
+        if i - 1 < dimensions{
+            for k in 0..dimensions{
+                basis[(i - 1) * dimensions + k] = edge[k] / residual;
+            }
+        }
+    }
+    smallest_ratio
+}
+
+// What NelderMeade needs from a simplex. Implemented by the heap-allocated Simplex and by
+// FixedSimplex, which keeps its vertices on the stack.
+pub trait Vertices{
+    // f64 or f32, for coordinates and values alike.
+    type Scalar: Real;
+
+    // dimensions + 1
+    fn vertex_count(&self) -> usize;
+    fn x(&self, index: usize) -> &[Self::Scalar];
+    fn x_mut(&mut self, index: usize) -> &mut [Self::Scalar];
+    fn value(&self, index: usize) -> Self::Scalar;
+    fn set_value(&mut self, index: usize, value: Self::Scalar);
+    // Heap-allocated copy of the vertex at index, as reported in results.
+    fn point(&self, index: usize) -> Point<Self::Scalar>;
+    // The vertices of simplex in this storage, e.g. when resuming from a Checkpoint. Fails
+    // unless there are n + 1 vertices of dimension n that fit.
+    fn from_simplex(simplex: Simplex<Self::Scalar>) -> Result<Self, Error> where Self: Sized;
+
+    fn dimensions(&self) -> usize{
+        self.x(0).len()
+    }
+
+    fn needed_points(&self) -> [usize; 3]{
+        needed_points(self.vertex_count(), |i| self.value(i))
+    }
+
+    fn best_index(&self) -> usize{
+        let mut best = 0;
+        for i in 0..self.vertex_count(){
+            if sanitize(self.value(i)) < sanitize(self.value(best)){
+                best = i;
+            }
+        }
+        best
+    }
+
+    // Largest minus smallest vertex value.
+    fn f_spread(&self) -> Self::Scalar{
+        let mut smallest = self.value(0);
+        let mut largest = self.value(0);
+        for i in 0..self.vertex_count(){
+            smallest = smallest.min(self.value(i));
+            largest = largest.max(self.value(i));
+        }
+        largest - smallest
+    }
+
+    // Largest distance between any two vertices.
+    fn diameter(&self) -> Self::Scalar{
+        let mut diameter = Self::Scalar::zero();
+        for i in 0..self.vertex_count(){
+            for j in (i + 1)..self.vertex_count(){
+                let squared = self.x(i).iter().zip(self.x(j)).fold(Self::Scalar::zero(), |sum, (a, b)| sum + (*a - *b).powi(2));
+                diameter = diameter.max(squared.powf(cast(0.5)));
+            }
+        }
+        diameter
+    }
+
+    // See Simplex::flatness. Does not allocate: basis holds dimensions^2 values, edge
+    // dimensions values.
+    fn flatness_with(&self, basis: &mut [Self::Scalar], edge: &mut [Self::Scalar]) -> Self::Scalar{
+        flatness_in(self.vertex_count(), |i| self.x(i), basis, edge)
+    }
+}
+
+// [smallest, second_largest, largest] among count values.
+pub fn needed_points<T: Real, V: Fn(usize) -> T>(count: usize, value: V) -> [usize; 3]{
+    // a NaN in a hand-built simplex ranks like +inf instead of breaking the ordering
+    let value = |i: usize| sanitize(value(i));
+    let mut needed_points: [usize; 3] = [0,1,2];
+    needed_points.sort_by(|a,b| value(*a).partial_cmp(&value(*b)).unwrap_or(Ordering::Equal));
+
+    for i in 3..count{
+        if value(i) > value(needed_points[2]){
+            needed_points[1] = needed_points[2];
+            needed_points[2] = i;
+        } else if value(i) > value(needed_points[1]){
+            needed_points[1] = i;
+        } else if value(i) < value(needed_points[0]){
+            needed_points[0] = i;
+        }
+    }
+    needed_points
+}
+
+// n + 1 finite points of dimension n, n at least 2, spanning a proper simplex.
+pub fn check_vertices<T: Real>(vertices: &[&[T]]) -> Result<(), Error>{
+    if vertices.len() <= 2{
+        return Err(Error::TooFewPoints{ found: vertices.len() });
+    }
+    for vertex in vertices.iter(){
+        if vertex.len() + 1 != vertices.len(){
+            return Err(Error::DimensionMismatch{ expected: vertices.len() - 1, found: vertex.len() });
+        }
+        if let Some(position) = vertex.iter().position(|v| !v.is_finite()){
+            return Err(Error::NonFiniteStart{ position: position });
+        }
+    }
+    if flatness(vertices) < cast(DEGENERACY_TOLERANCE){
+        return Err(Error::DegenerateSimplex);
+    }
+    Ok(())
+}
+
+// n + 1 points of dimension n, n at least 2. Unlike check_vertices this accepts any shape, for
+// a simplex that has been iterated on.
+fn check_shape<T>(simplex: &Simplex<T>, dimensions: usize) -> Result<(), Error>{
+    if simplex.points.len() <= 2{
+        return Err(Error::TooFewPoints{ found: simplex.points.len() });
+    }
+    if simplex.points.len() != dimensions + 1{
+        return Err(Error::DimensionMismatch{ expected: dimensions + 1, found: simplex.points.len() });
+    }
+    if let Some(point) = simplex.points.iter().find(|p| p.x.len() != dimensions){
+        return Err(Error::DimensionMismatch{ expected: dimensions, found: point.x.len() });
+    }
+    Ok(())
+}
+
+// A guess to build an initial simplex around: at least 2 finite coordinates, and bounds (if any)
+// of the same dimension.
+pub fn check_guess<T: Real>(point: &[T], bounds: Option<&Bounds>) -> Result<(), Error>{
+    if point.len() < 2{
+        return Err(Error::TooFewDimensions{ found: point.len() });
+    }
+    if let Some(position) = point.iter().position(|v| !v.is_finite()){
+        return Err(Error::NonFiniteStart{ position: position });
+    }
+    if let Some(bounds) = bounds{
+        if bounds.dimensions() != point.len(){
+            return Err(Error::DimensionMismatch{ expected: point.len(), found: bounds.dimensions() });
+        }
+    }
+    Ok(())
+}
 
-        return Ok(Simplex{points: points_vector.into_boxed_slice()});
+// All vertices go to the objective as one batch, so a parallel or batched objective can
+// evaluate them concurrently.
+fn evaluate_vertices<T: Real, O: Objective<T> + ?Sized>(vertices: &[&[T]], func: &mut O) -> Result<Vec<T>, Error>{
+    check_dimensions(func, vertices.first().map_or(0, |x| x.len()))?;
+    let values: Vec<T> = func.evaluate_batch(vertices).into_iter().map(sanitize).collect();
+    if values.len() != vertices.len(){
+        return Err(Error::DimensionMismatch{ expected: vertices.len(), found: values.len() });
+    }
+    if values.iter().all(|v| *v == T::infinity()){
+        return Err(Error::NonFiniteStartValue);
+    }
+    Ok(values)
+}
+
+impl<T: Real> Simplex<T>{
+    pub fn new<O: Objective<T> + ?Sized>(mut points: Vec<Vec<T>>, func: &mut O) -> Result<Simplex<T>, Error>{
+        let vertices: Vec<&[T]> = points.iter().map(|p| &p[..]).collect();
+        check_vertices(&vertices)?;
+
+        points.reverse();
+        Simplex::evaluate(points.into_iter().map(|p| p.into_boxed_slice()).collect(), func)
     }
     
-    pub fn from_guess(point: Box<[f64]>, step: f64, func: fn(&[f64]) -> f64) -> Result<Simplex,String>{
+    pub fn from_guess<O: Objective<T> + ?Sized>(point: Box<[T]>, step: T, bounds: Option<&Bounds>, func: &mut O) -> Result<Simplex<T>, Error>{
+        let vertices = Simplex::guess_vertices(point, step, bounds)?;
+        Simplex::evaluate(vertices, func)
+    }
+
+    // The starting simplex chosen by initial, see InitialSimplex.
+    pub fn initial<O: Objective<T> + ?Sized>(point: Box<[T]>, initial: &InitialSimplex, bounds: Option<&Bounds>, func: &mut O) -> Result<Simplex<T>, Error>{
+        let vertices = initial.vertices(point, bounds)?;
+        Simplex::evaluate(vertices, func)
+    }
+
+    fn evaluate<O: Objective<T> + ?Sized>(vertices: Vec<Box<[T]>>, func: &mut O) -> Result<Simplex<T>, Error>{
+        let vertex_slices: Vec<&[T]> = vertices.iter().map(|p| &p[..]).collect();
+        let values = evaluate_vertices(&vertex_slices, func)?;
+        Ok(Simplex{points: vertices.into_iter().zip(values).map(|(x, value)| Point{x: x, value: value}).collect()})
+    }
+
+    // The vertices from_guess builds, without evaluating them.
+    pub fn guess_vertices(point: Box<[T]>, step: T, bounds: Option<&Bounds>) -> Result<Vec<Box<[T]>>, Error>{
         let dimensions: usize = point.len();
-        let mut points_vector:Vec<Point> = Vec::with_capacity(dimensions + 1);
-        let mut centroid:Box<[f64]> = point;
-        let mut temp_point:Box<[f64]> = centroid.clone();
-        let mut radius: f64 = step;
+        check_guess(&point, bounds)?;
+        if !step.is_finite(){
+            return Err(Error::InvalidStep);
+        }
+        let mut vertices: Vec<Box<[T]>> = Vec::with_capacity(dimensions + 1);
+        let mut centroid:Box<[T]> = point;
+        let mut temp_point:Box<[T]> = centroid.clone();
+        let mut radius: T = step;
         for i in 0..dimensions{
 
-            temp_point = match add_to_index(centroid.clone(), i, radius){
-                Err(str) => panic!("{}", str),
-                Ok(point) => point
-            };
+            temp_point = add_to_index(centroid.clone(), i, radius)?;
+
+            centroid = add_to_index(centroid, i, radius * cast(-0.5))?;
+
+            radius *= cast(3.0_f64.powf(0.5)/2.0);
+
+            if let Some(bounds) = bounds{
+                temp_point = bounds.project(temp_point)?;
+            }
+
+            vertices.push(temp_point.clone());
+
+        }
+        temp_point[dimensions - 1] = -temp_point[dimensions - 1];
+        if let Some(bounds) = bounds{
+            temp_point = bounds.project(temp_point)?;
+        }
+        vertices.push(temp_point);
+
+        // Clipping to the bounds can flatten the simplex when the guess sits on a bound.
+        let vertex_slices: Vec<&[T]> = vertices.iter().map(|p| &p[..]).collect();
+        if flatness(&vertex_slices) < cast(DEGENERACY_TOLERANCE){
+            return Err(Error::DegenerateSimplex);
+        }
+        Ok(vertices)
+    }
+
+    pub fn needed_points(&self) -> [usize; 3]{
+        Vertices::needed_points(self)
+    }
+
+    // Centroid of all vertices except the one at index.
+    pub fn centroid_without(&self, index: usize) -> Box<[T]>{
+        let mut centroid: Box<[T]> = vec![T::zero(); self.dimensions()].into_boxed_slice();
+        for i in 0..self.points.len() {
+            if i != index{
+                add_assign(&mut centroid, &self.points[i].x);
+            }
+        }
+        let scale: T = T::one() / cast((self.points.len() - 1) as f64);
+        for c in centroid.iter_mut(){
+            *c *= scale;
+        }
+        centroid
+    }
+
+    pub fn dimensions(&self) -> usize{
+        Vertices::dimensions(self)
+    }
+
+    pub fn flatness(&self) -> T{
+        let vertices: Vec<&[T]> = self.points.iter().map(|p| &p.x[..]).collect();
+        flatness(&vertices)
+    }
+
+    pub fn is_degenerate(&self, tolerance: f64) -> bool{
+        self.flatness() < cast(tolerance)
+    }
+
+    pub fn best(&self) -> &Point<T>{
+        &self.points[self.best_index()]
+    }
+
+    pub fn f_spread(&self) -> T{
+        Vertices::f_spread(self)
+    }
+
+    pub fn diameter(&self) -> T{
+        Vertices::diameter(self)
+    }
+
+}
+
+impl<T: Real> Vertices for Simplex<T>{
+    type Scalar = T;
+
+    fn vertex_count(&self) -> usize{
+        self.points.len()
+    }
+
+    fn x(&self, index: usize) -> &[T]{
+        &self.points[index].x
+    }
+
+    fn x_mut(&mut self, index: usize) -> &mut [T]{
+        &mut self.points[index].x
+    }
+
+    fn value(&self, index: usize) -> T{
+        self.points[index].value
+    }
+
+    fn set_value(&mut self, index: usize, value: T){
+        self.points[index].value = value;
+    }
+
+    fn point(&self, index: usize) -> Point<T>{
+        self.points[index].clone()
+    }
+
+    fn from_simplex(simplex: Simplex<T>) -> Result<Simplex<T>, Error>{
+        check_shape(&simplex, simplex.points.len().saturating_sub(1))?;
+        Ok(simplex)
+    }
+}
+
+// Const-generic counterpart of Simplex for small problems whose dimension is known at compile
+// time. The vertices live inline, and vertices, guesses and objectives (see objective::Fixed)
+// of another dimension do not type-check. Stable Rust cannot write an array of N + 1 vertices,
+// hence the first vertex and the remaining N.
+#[derive(Clone, Copy)]
+pub struct FixedSimplex<const N: usize, T = f64>{
+    pub first: FixedPoint<N, T>,
+    pub rest: [FixedPoint<N, T>; N],
+}
 
-            centroid = match add_to_index(centroid, i, radius * -0.5){
-                Err(str) => panic!("{}", str),
-                Ok(point) => point
-            };
+impl<const N: usize, T: Real> FixedSimplex<N, T>{
+    // Referenced by the constructors, so FixedSimplex<0> and FixedSimplex<1> fail to compile.
+    const AT_LEAST_TWO_DIMENSIONS: () = assert!(N >= 2, "FixedSimplex needs at least 2 dimensions");
+
+    pub fn new<O: Objective<T> + ?Sized>(first: [T; N], rest: [[T; N]; N], func: &mut O) -> Result<FixedSimplex<N, T>, Error>{
+        let () = Self::AT_LEAST_TWO_DIMENSIONS;
+        let mut vertices: Vec<&[T]> = Vec::with_capacity(N + 1);
+        vertices.push(&first);
+        vertices.extend(rest.iter().map(|x| &x[..]));
+        check_vertices(&vertices)?;
+        let values = evaluate_vertices(&vertices, func)?;
+        let mut simplex = FixedSimplex{
+            first: FixedPoint{x: first, value: values[0]},
+            rest: [FixedPoint{x: [T::zero(); N], value: T::zero()}; N]
+        };
+        for i in 0..N{
+            simplex.rest[i] = FixedPoint{x: rest[i], value: values[i + 1]};
+        }
+        Ok(simplex)
+    }
 
-            radius *= 3.0_f64.powf(0.5)/2.0;
+    // Same vertices as Simplex::from_guess.
+    pub fn from_guess<O: Objective<T> + ?Sized>(point: [T; N], step: T, bounds: Option<&Bounds>, func: &mut O) -> Result<FixedSimplex<N, T>, Error>{
+        let () = Self::AT_LEAST_TWO_DIMENSIONS;
+        let vertices = Simplex::guess_vertices(Box::new(point), step, bounds)?;
+        FixedSimplex::evaluate(vertices, func)
+    }
 
-            points_vector.push(Point{
-                x: temp_point.clone(),
-                value: func(&temp_point)
-            });
+    // Same vertices as Simplex::initial.
+    pub fn initial<O: Objective<T> + ?Sized>(point: [T; N], initial: &InitialSimplex, bounds: Option<&Bounds>, func: &mut O) -> Result<FixedSimplex<N, T>, Error>{
+        let () = Self::AT_LEAST_TWO_DIMENSIONS;
+        let vertices = initial.vertices(Box::new(point), bounds)?;
+        FixedSimplex::evaluate(vertices, func)
+    }
 
+    // vertices: N + 1 points of dimension N.
+    fn evaluate<O: Objective<T> + ?Sized>(vertices: Vec<Box<[T]>>, func: &mut O) -> Result<FixedSimplex<N, T>, Error>{
+        let vertex_slices: Vec<&[T]> = vertices.iter().map(|p| &p[..]).collect();
+        let values = evaluate_vertices(&vertex_slices, func)?;
+        let mut simplex = FixedSimplex{
+            first: FixedPoint{x: [T::zero(); N], value: T::zero()},
+            rest: [FixedPoint{x: [T::zero(); N], value: T::zero()}; N]
+        };
+        for i in 0..=N{
+            simplex.x_mut(i).copy_from_slice(&vertices[i]);
+            simplex.set_value(i, values[i]);
         }
-        temp_point[dimensions - 1] *= -1.0;
-        
-        let last_value = func(&temp_point);
+        Ok(simplex)
+    }
+
+    pub fn vertex(&self, index: usize) -> &FixedPoint<N, T>{
+        if index == 0 { &self.first } else { &self.rest[index - 1] }
+    }
 
-        points_vector.push(Point{ 
-            x: temp_point, 
-            value: last_value
-        });
-        return Ok(Simplex { points: points_vector.into_boxed_slice() });
+    fn vertex_mut(&mut self, index: usize) -> &mut FixedPoint<N, T>{
+        if index == 0 { &mut self.first } else { &mut self.rest[index - 1] }
     }
 
+    pub fn best(&self) -> &FixedPoint<N, T>{
+        self.vertex(self.best_index())
+    }
 }
 
-impl fmt::Display for Simplex {
+impl<const N: usize, T: Real> Vertices for FixedSimplex<N, T>{
+    type Scalar = T;
+
+    fn vertex_count(&self) -> usize{
+        N + 1
+    }
+
+    fn x(&self, index: usize) -> &[T]{
+        &self.vertex(index).x
+    }
+
+    fn x_mut(&mut self, index: usize) -> &mut [T]{
+        &mut self.vertex_mut(index).x
+    }
+
+    fn value(&self, index: usize) -> T{
+        self.vertex(index).value
+    }
+
+    fn set_value(&mut self, index: usize, value: T){
+        self.vertex_mut(index).value = value;
+    }
+
+    fn point(&self, index: usize) -> Point<T>{
+        (*self.vertex(index)).into()
+    }
+
+    fn from_simplex(simplex: Simplex<T>) -> Result<FixedSimplex<N, T>, Error>{
+        check_shape(&simplex, N)?;
+        let mut fixed = FixedSimplex{
+            first: FixedPoint{x: [T::zero(); N], value: T::zero()},
+            rest: [FixedPoint{x: [T::zero(); N], value: T::zero()}; N]
+        };
+        for (i, point) in simplex.points.iter().enumerate(){
+            fixed.x_mut(i).copy_from_slice(&point.x);
+            fixed.set_value(i, point.value);
+        }
+        Ok(fixed)
+    }
+}
+
+impl<T: Real> fmt::Display for Simplex<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
         let mut representation: String = String::new();
         representation.push_str("Points: \n");
@@ -90,4 +479,51 @@ impl fmt::Display for Simplex {
         }
         write!(f, "{}", representation)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{nelder_meade::NelderMeade, termination::Termination};
+
+    #[test]
+    fn rejects_invalid_vertices(){
+        let mut func = |x: &[f64]| x[0];
+        assert_eq!(Simplex::new(vec![vec![0.0], vec![1.0]], &mut func).err(), Some(Error::TooFewPoints{ found: 2 }));
+        assert_eq!(Simplex::new(vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![2.0, 0.0]], &mut func).err(), Some(Error::DegenerateSimplex));
+        assert_eq!(Simplex::new(vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![0.0]], &mut func).err(), Some(Error::DimensionMismatch{ expected: 2, found: 1 }));
+        assert_eq!(Simplex::new(vec![vec![0.0, 0.0], vec![1.0, f64::NAN], vec![0.0, 1.0]], &mut func).err(), Some(Error::NonFiniteStart{ position: 1 }));
+        assert_eq!(Simplex::from_guess(vec![0.0, 0.0].into_boxed_slice(), 1.0, None, &mut |_: &[f64]| None::<f64>).err(), Some(Error::NonFiniteStartValue));
+        assert_eq!(Simplex::from_guess(vec![0.0, 0.0].into_boxed_slice(), f64::NAN, None, &mut func).err(), Some(Error::InvalidStep));
+    }
+
+    #[test]
+    fn fixed_and_dynamic_simplices_agree(){
+        let mut func = |x: &[f64]| x[0] * x[0] + 3.0 * x[1];
+        let simplex = Simplex::from_guess(vec![1.0, 2.0, 3.0].into_boxed_slice(), 0.5, None, &mut func).unwrap();
+        let fixed = FixedSimplex::from_guess([1.0, 2.0, 3.0], 0.5, None, &mut func).unwrap();
+        for i in 0..4{
+            assert_eq!(simplex.x(i), fixed.x(i));
+            assert_eq!(simplex.value(i), fixed.value(i));
+        }
+        assert_eq!(simplex.needed_points(), Vertices::needed_points(&fixed));
+        assert_eq!(simplex.diameter(), fixed.diameter());
+    }
+
+    // Objectives may capture data and keep state between evaluations.
+    #[test]
+    fn fits_captured_data(){
+        let data: Vec<(f64, f64)> = (0..20).map(|i| { let t = i as f64 / 4.0; (t, 3.0 - 0.5 * t) }).collect();
+        let mut evaluations = 0;
+        let mut residuals = |p: &[f64]| {
+            evaluations += 1;
+            data.iter().map(|(t, y)| (p[0] + p[1] * t - y).powi(2)).sum::<f64>()
+        };
+        let simplex = Simplex::from_guess(vec![0.0, 0.0].into_boxed_slice(), 1.0, None, &mut residuals).unwrap();
+        let mut nelder_meade = NelderMeade::new(simplex, residuals);
+        let result = nelder_meade.run(&Termination::new().max_iterations(5_000).x_tol(1e-12));
+        assert!((result.best.x[0] - 3.0).abs() < 1e-6 && (result.best.x[1] + 0.5).abs() < 1e-6, "{:?}", result.best.x);
+        drop(nelder_meade);
+        assert_eq!(evaluations, result.evaluations);
+    }
+}
@@ -0,0 +1,428 @@
+use std::time::Instant;
+
+use crate::{point::Point, simplex::Simplex, nelder_meade::NelderMeade, objective::{Objective, sanitize}, real::{Real, cast}, bounds::Bounds, coefficients::Coefficients, error::Error, optimizer::{Optimizer, evaluate_points, feasible_guess}, termination::{Termination, StopReason}, result::{OptimizationResult, MoveCounts, IterationRecord}};
+
+// Rowan's Subplex (1990). Nelder-Mead gets slow well before ten dimensions, so every iteration
+// splits the variables into subspaces of a few variables each and runs NelderMeade on one
+// subspace after the other, holding the remaining variables fixed. The partition follows the
+// progress of the previous iteration: the coordinates that moved the most are searched
+// together. Each subspace search stops once its simplex has shrunk by simplex_reduction, and
+// the step sizes of the next iteration are scaled by how far the whole iteration moved.
+pub struct Subplex<O, T = f64> where O: Objective<T>{
+    pub func: O,
+    // Used by the Nelder-Mead search in every subspace.
+    pub coefficients: Coefficients,
+    // A subspace search stops when its simplex has shrunk to this fraction of the initial size.
+    pub simplex_reduction: f64,
+    // Bounds the factor the step sizes change by per iteration to [step_scale_limit, 1 / step_scale_limit].
+    pub step_scale_limit: f64,
+    pub iterations: usize,
+    pub evaluations: usize,
+    // Taken by the subspace searches, summed up.
+    pub moves: MoveCounts,
+    // Keep a per-iteration record of the best value and step measure in run().
+    pub record_history: bool,
+    // Sizes a subspace may have, see set_subspace_sizes.
+    min_subspace: usize,
+    max_subspace: usize,
+    // The guess was projected onto them, see new.
+    bounds: Option<Bounds>,
+    // The coordinates the bounds leave room in. Those with lower == upper keep their value and
+    // are left out of every subspace.
+    free: Vec<usize>,
+    best: Point<T>,
+    // Signed edge length of the initial simplex along every coordinate.
+    steps: Box<[T]>,
+    // Move of the last iteration, the step sizes to begin with.
+    progress: Box<[T]>,
+    subspaces: Vec<Vec<usize>>,
+    // Of the last iteration, for Termination::f_tol and Termination::x_tol: the largest spread of
+    // the vertex values of a subspace simplex, and Rowan's step measure.
+    spread: T,
+    size: T,
+}
+
+// The objective restricted to the coordinates at indices, the other coordinates taken from x.
+struct Subspace<'a, O, T>{
+    func: &'a mut O,
+    x: Box<[T]>,
+    indices: &'a [usize],
+}
+
+impl<'a, O, T: Real> Subspace<'a, O, T>{
+    fn full(&self, y: &[T]) -> Box<[T]>{
+        let mut x = self.x.clone();
+        for (k, i) in self.indices.iter().enumerate(){
+            x[*i] = y[k];
+        }
+        x
+    }
+}
+
+impl<'a, O: Objective<T>, T: Real> Objective<T> for Subspace<'a, O, T>{
+    fn evaluate(&mut self, y: &[T]) -> T{
+        for (k, i) in self.indices.iter().enumerate(){
+            self.x[*i] = y[k];
+        }
+        self.func.evaluate(&self.x)
+    }
+
+    fn evaluate_batch(&mut self, points: &[&[T]]) -> Vec<T>{
+        let full: Vec<Box<[T]>> = points.iter().map(|y| self.full(y)).collect();
+        let slices: Vec<&[T]> = full.iter().map(|x| &x[..]).collect();
+        self.func.evaluate_batch(&slices)
+    }
+
+    fn prefers_batches(&self) -> bool{
+        self.func.prefers_batches()
+    }
+}
+
+// Splits the coordinates, ordered by decreasing |progress|, into consecutive groups of
+// min_size..=max_size. Every group ends where the average progress of the coordinates up to
+// and including it exceeds the average of the coordinates left over by as much as possible,
+// i.e. at a sudden drop in progress. When n cannot be split into allowed sizes, the leftover
+// coordinates join the last group instead of forming one smaller than min_size.
+fn partition<T: Real>(progress: &[T], min_size: usize, max_size: usize) -> Vec<Vec<usize>>{
+    let n = progress.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|a, b| progress[*b].abs().partial_cmp(&progress[*a].abs()).unwrap_or(std::cmp::Ordering::Equal));
+    let magnitude = |k: usize| progress[order[k]].abs().to_f64().unwrap_or(0.0);
+    // the rest has to split into groups of allowed sizes as well
+    let splittable = |rest: usize| rest == 0 || rest.div_ceil(max_size) * min_size <= rest;
+
+    let total: f64 = (0..n).map(magnitude).sum();
+    let mut subspaces: Vec<Vec<usize>> = Vec::new();
+    let mut start = 0;
+    // progress of the coordinates before start
+    let mut before = 0.0;
+    while start < n{
+        let remaining = n - start;
+        let mut up_to = before;
+        let mut chosen = remaining.min(max_size);
+        let mut best_score = f64::NEG_INFINITY;
+        for size in 1..=remaining.min(max_size){
+            up_to += magnitude(start + size - 1);
+            if size < min_size || !splittable(remaining - size){
+                continue;
+            }
+            let end = start + size;
+            let score = if end < n { up_to / end as f64 - (total - up_to) / (n - end) as f64 } else { total / n as f64 };
+            if score > best_score{
+                best_score = score;
+                chosen = size;
+            }
+        }
+        before += (start..start + chosen).map(magnitude).sum::<f64>();
+        match subspaces.last_mut(){
+            // only a remainder no allowed size fits can be this small
+            Some(last) if chosen < min_size => last.extend_from_slice(&order[start..start + chosen]),
+            _ => subspaces.push(order[start..start + chosen].to_vec())
+        }
+        start += chosen;
+    }
+    subspaces
+}
+
+impl<O: Objective<T>, T: Real> Subplex<O, T>{
+
+    // Starts from the guess projected onto the bounds, if any.
+    pub fn new(guess: Box<[T]>, step: T, bounds: Option<&Bounds>, func: O) -> Result<Subplex<O, T>, Error>{
+        let steps = vec![step; guess.len()].into_boxed_slice();
+        Subplex::with_steps(guess, steps, bounds, func)
+    }
+
+    // One initial step size per coordinate, for variables on different scales. At least 2
+    // coordinates have to be free, i.e. not fixed by lower == upper.
+    pub fn with_steps(guess: Box<[T]>, steps: Box<[T]>, bounds: Option<&Bounds>, mut func: O) -> Result<Subplex<O, T>, Error>{
+        let guess = feasible_guess(guess, bounds, &func)?;
+        if steps.len() != guess.len(){
+            return Err(Error::DimensionMismatch{ expected: guess.len(), found: steps.len() });
+        }
+        if steps.iter().any(|s| !s.is_finite() || *s == T::zero()){
+            return Err(Error::InvalidStep);
+        }
+        let free: Vec<usize> = (0..guess.len()).filter(|i| bounds.is_none_or(|b| b.lower[*i] < b.upper[*i])).collect();
+        if free.len() < 2{
+            return Err(Error::TooFewDimensions{ found: free.len() });
+        }
+        let value = sanitize(func.evaluate(&guess));
+        Ok(Subplex{
+            func: func,
+            bounds: bounds.cloned(),
+            free: free,
+            coefficients: Coefficients::standard(),
+            simplex_reduction: 0.25,
+            step_scale_limit: 0.1,
+            iterations: 0,
+            evaluations: 1,
+            moves: MoveCounts::default(),
+            record_history: false,
+            min_subspace: 2,
+            max_subspace: 5,
+            best: Point{x: guess, value: value},
+            progress: steps.clone(),
+            steps: steps,
+            subspaces: Vec::new(),
+            spread: T::infinity(),
+            size: T::infinity()
+        })
+    }
+
+    // At least 2, the least Nelder-Mead works with. Problems with fewer than min coordinates are
+    // searched as one subspace, and a remainder too small for a subspace of its own joins the
+    // last one, so a subspace can end up larger than max.
+    pub fn set_subspace_sizes(&mut self, min: usize, max: usize) -> Result<(), Error>{
+        if min < 2 || min > max{
+            return Err(Error::InvalidSubspaceSizes{ min: min, max: max });
+        }
+        self.min_subspace = min;
+        self.max_subspace = max;
+        Ok(())
+    }
+
+    pub fn subspace_sizes(&self) -> (usize, usize){
+        (self.min_subspace, self.max_subspace)
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds>{
+        self.bounds.as_ref()
+    }
+
+    pub fn best(&self) -> &Point<T>{
+        &self.best
+    }
+
+    // The partition used by the last iteration.
+    pub fn subspaces(&self) -> &[Vec<usize>]{
+        &self.subspaces
+    }
+
+    // Vertices of the initial simplex of a subspace: the best point and one step along every
+    // coordinate, taken the other way if a bound leaves no room.
+    fn subspace_simplex(&mut self, indices: &[usize], bounds: Option<&Bounds>) -> Simplex<T>{
+        let base: Box<[T]> = indices.iter().map(|i| self.best.x[*i]).collect();
+        let vertices: Vec<Box<[T]>> = (0..indices.len()).map(|k| {
+            let mut x = base.clone();
+            for step in [self.steps[indices[k]], -self.steps[indices[k]]]{
+                x[k] = base[k] + step;
+                if let Some(bounds) = bounds{
+                    bounds.clamp(&mut x);
+                }
+                if x[k] != base[k]{
+                    break;
+                }
+            }
+            x
+        }).collect();
+        let mut subspace = Subspace{func: &mut self.func, x: self.best.x.clone(), indices: indices};
+        let slices: Vec<&[T]> = vertices.iter().map(|x| &x[..]).collect();
+        let values = evaluate_points(&mut subspace, &mut self.evaluations, &slices);
+        let mut points = vec![Point{x: base, value: self.best.value}];
+        points.extend(vertices.into_iter().zip(values).map(|(x, value)| Point{x: x, value: value}));
+        Simplex{points: points.into_boxed_slice()}
+    }
+
+    // Nelder-Mead on the subspace until its simplex has shrunk by simplex_reduction or one of
+    // the limits is reached, which the reason is returned for.
+    fn search_subspace(&mut self, indices: &[usize], limits: &Termination, start: Instant, start_evaluations: usize) -> Option<StopReason>{
+        let bounds: Option<Bounds> = self.bounds.as_ref().map(|b| Bounds{
+            lower: indices.iter().map(|i| b.lower[*i]).collect(),
+            upper: indices.iter().map(|i| b.upper[*i]).collect()
+        });
+        let simplex = self.subspace_simplex(indices, bounds.as_ref());
+        let criteria = Termination{
+            max_iterations: None,
+            max_evaluations: limits.max_evaluations.map(|m| (start_evaluations + m).saturating_sub(self.evaluations)),
+            f_tol: None,
+            x_tol: Some(self.simplex_reduction * simplex.diameter().to_f64().unwrap_or(0.0)),
+            converged: None,
+            time_limit: limits.time_limit.map(|l| l.saturating_sub(start.elapsed())),
+            cancellation: limits.cancellation.clone()
+        };
+        let subspace = Subspace{func: &mut self.func, x: self.best.x.clone(), indices: indices};
+        let mut search = NelderMeade::with_coefficients(simplex, subspace, self.coefficients);
+        // the box and the simplex both have one coordinate per index
+        search.bounds = bounds;
+        // the vertices were counted in subspace_simplex
+        search.evaluations = 0;
+        let result = search.run(&criteria);
+        self.spread = self.spread.max(search.simplex.f_spread());
+        self.evaluations += result.evaluations;
+        self.moves.add(&search.moves);
+        if result.best.value < self.best.value{
+            for (k, i) in indices.iter().enumerate(){
+                self.best.x[*i] = result.best.x[k];
+            }
+            self.best.value = result.best.value;
+        }
+        match result.stop_reason{
+            StopReason::MaxEvaluations | StopReason::TimeLimit | StopReason::Cancelled => Some(result.stop_reason),
+            _ => None
+        }
+    }
+
+    // Searches every subspace once and adapts the step sizes. Returns early when one of the
+    // limits (evaluations, time, cancellation) is reached.
+    fn iterate(&mut self, limits: &Termination, start: Instant, start_evaluations: usize) -> Option<StopReason>{
+        let n = self.best.x.len();
+        let max_size = self.max_subspace.min(self.free.len());
+        let min_size = self.min_subspace.min(max_size);
+        let free_progress: Vec<T> = self.free.iter().map(|i| self.progress[*i]).collect();
+        self.subspaces = partition(&free_progress, min_size, max_size).into_iter()
+            .map(|subspace| subspace.into_iter().map(|k| self.free[k]).collect())
+            .collect();
+        let before = self.best.x.clone();
+        self.spread = T::zero();
+        let subspaces = std::mem::take(&mut self.subspaces);
+        for indices in &subspaces{
+            if let Some(reason) = self.search_subspace(indices, limits, start, start_evaluations){
+                self.subspaces = subspaces;
+                return Some(reason);
+            }
+        }
+        self.subspaces = subspaces;
+
+        for i in 0..n{
+            self.progress[i] = self.best.x[i] - before[i];
+        }
+        let moved = self.progress.iter().fold(T::zero(), |sum, d| sum + d.abs());
+        let stepped = self.free.iter().fold(T::zero(), |sum, i| sum + self.steps[*i].abs());
+        let limit: T = cast(self.step_scale_limit);
+        let scale = if self.subspaces.len() > 1{
+            (moved / stepped).max(limit).min(T::one() / limit)
+        } else {
+            cast(self.simplex_reduction)
+        };
+        for i in self.free.iter().copied(){
+            // along the last move, or back the other way if the coordinate did not move
+            let direction = if self.progress[i] != T::zero() { self.progress[i].signum() } else { -self.steps[i].signum() };
+            self.steps[i] = direction * self.steps[i].abs() * scale;
+        }
+        let reduction: T = cast(self.simplex_reduction);
+        self.size = self.free.iter().fold(T::zero(), |size, i| size.max(self.progress[*i].abs()).max(reduction * self.steps[*i].abs()));
+        None
+    }
+
+    // One iteration: every subspace searched once. Of limits, only max_evaluations, time_limit
+    // and cancellation apply, counted from this call. Returns the one that cut the iteration
+    // short, which then is not counted in iterations.
+    pub fn step(&mut self, limits: &Termination) -> Option<StopReason>{
+        let reason = self.iterate(limits, Instant::now(), self.evaluations);
+        if reason.is_none(){
+            self.iterations += 1;
+        }
+        reason
+    }
+
+    pub fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        let start = Instant::now();
+        let start_iterations = self.iterations;
+        let start_evaluations = self.evaluations;
+        let mut history: Option<Vec<IterationRecord<T>>> = if self.record_history { Some(Vec::new()) } else { None };
+        let stop_reason: StopReason = loop {
+            if let Some(reason) = criteria.check_progress(self.spread, self.size, self.iterations - start_iterations, self.evaluations - start_evaluations, start){
+                break reason;
+            }
+            if let Some(reason) = self.iterate(criteria, start, start_evaluations){
+                break reason;
+            }
+            self.iterations += 1;
+            if let Some(history) = &mut history{
+                history.push(IterationRecord{
+                    iteration: self.iterations,
+                    best_value: self.best.value,
+                    diameter: self.size
+                });
+            }
+        };
+        OptimizationResult{
+            best: self.best.clone(),
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            moves: Some(self.moves),
+            restarts: 0,
+            history: history,
+            stop_reason: stop_reason
+        }
+    }
+}
+
+impl<O: Objective<T>, T: Real> Optimizer for Subplex<O, T>{
+    type Scalar = T;
+
+    fn run(&mut self, criteria: &Termination) -> OptimizationResult<T>{
+        Subplex::run(self, criteria)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn sphere(x: &[f64]) -> f64{
+        x.iter().map(|xi| (xi - 1.0) * (xi - 1.0)).sum()
+    }
+
+    #[test]
+    fn partition_never_leaves_a_single_coordinate(){
+        for n in 2..12{
+            for (min, max) in [(2, 2), (2, 3), (2, 5), (3, 4), (4, 4)]{
+                let progress: Vec<f64> = (0..n).map(|i| (i as f64 * 1.7).sin()).collect();
+                let subspaces = partition(&progress, min.min(n), max.min(n));
+                let mut covered: Vec<usize> = subspaces.iter().flatten().copied().collect();
+                covered.sort();
+                assert_eq!(covered, (0..n).collect::<Vec<usize>>());
+                assert!(subspaces.iter().all(|s| s.len() >= min.min(n)), "n {} sizes {}..{}: {:?}", n, min, max, subspaces);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_subspace_sizes(){
+        let mut subplex = Subplex::new(vec![0.0; 3].into_boxed_slice(), 1.0, None, sphere).unwrap();
+        assert_eq!(subplex.set_subspace_sizes(1, 2), Err(Error::InvalidSubspaceSizes{ min: 1, max: 2 }));
+        assert_eq!(subplex.set_subspace_sizes(3, 2), Err(Error::InvalidSubspaceSizes{ min: 3, max: 2 }));
+        assert_eq!(subplex.subspace_sizes(), (2, 5));
+    }
+
+    #[test]
+    fn minimizes_with_an_uneven_partition(){
+        let mut subplex = Subplex::new(vec![0.0; 3].into_boxed_slice(), 1.0, None, sphere).unwrap();
+        subplex.set_subspace_sizes(2, 2).unwrap();
+        let result = subplex.run(&Termination::new().max_evaluations(5_000).x_tol(1e-8));
+        assert!(result.best.value < 1e-10, "{}", result.best.value);
+    }
+
+    #[test]
+    fn minimizes_a_larger_problem(){
+        let mut subplex = Subplex::new(vec![0.0; 12].into_boxed_slice(), 1.0, None, sphere).unwrap();
+        let result = subplex.run(&Termination::new().max_evaluations(50_000).x_tol(1e-8));
+        assert!(result.best.value < 1e-10, "{}", result.best.value);
+        assert!(subplex.subspaces().len() > 1);
+    }
+
+    #[test]
+    fn leaves_fixed_coordinates_alone(){
+        let bounds = Bounds::new(vec![-2.0, 0.3, -2.0, -2.0, 0.0], vec![2.0, 0.3, 2.0, 2.0, 0.0]).unwrap();
+        let mut subplex = Subplex::new(vec![0.0; 5].into_boxed_slice(), 1.0, Some(&bounds), sphere).unwrap();
+        subplex.set_subspace_sizes(2, 2).unwrap();
+        let result = subplex.run(&Termination::new().max_evaluations(5_000).x_tol(1e-8));
+        assert_eq!((result.best.x[1], result.best.x[4]), (0.3, 0.0));
+        assert!([0, 2, 3].iter().all(|i| (result.best.x[*i] - 1.0).abs() < 1e-6), "{:?}", result.best.x);
+        assert!(subplex.subspaces().iter().flatten().all(|i| ![1, 4].contains(i)));
+
+        let bounds = Bounds::new(vec![-2.0, 0.3, 0.3], vec![2.0, 0.3, 0.3]).unwrap();
+        assert_eq!(Subplex::new(vec![0.0; 3].into_boxed_slice(), 1.0, Some(&bounds), sphere).err(), Some(Error::TooFewDimensions{ found: 1 }));
+    }
+
+    #[test]
+    fn counts_the_iterations_of_step(){
+        let mut subplex = Subplex::new(vec![0.0; 6].into_boxed_slice(), 1.0, None, sphere).unwrap();
+        assert_eq!(subplex.step(&Termination::new()), None);
+        assert_eq!(subplex.step(&Termination::new()), None);
+        assert_eq!(subplex.iterations, 2);
+        assert_eq!(subplex.step(&Termination::new().max_evaluations(3)), Some(StopReason::MaxEvaluations));
+        assert_eq!(subplex.iterations, 2);
+    }
+}
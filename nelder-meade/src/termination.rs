@@ -0,0 +1,203 @@
+use std::{fmt, time::{Duration, Instant}};
+
+use serde::{Serialize, Deserialize};
+
+use crate::{simplex::Vertices, real::{Real, cast}, observer::CancellationToken};
+
+// A set of stopping criteria. A run stops as soon as any of the criteria that are set is met.
+#[derive(Clone)]
+pub struct Termination{
+    pub max_iterations: Option<usize>,
+    pub max_evaluations: Option<usize>,
+    // Largest minus smallest vertex value. Optimizers without a simplex use their own measure
+    // of how much the values still change, see Optimizer.
+    pub f_tol: Option<f64>,
+    // Largest distance between two vertices, or the step size of optimizers without a simplex.
+    pub x_tol: Option<f64>,
+    // (f_tol, x_tol) that must both be met in the same iteration. A symmetric start can give
+    // every vertex the same value, which f_tol alone takes for convergence.
+    pub converged: Option<(f64, f64)>,
+    pub time_limit: Option<Duration>,
+    pub cancellation: Option<CancellationToken>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason{
+    MaxIterations,
+    MaxEvaluations,
+    FunctionTolerance,
+    StepTolerance,
+    // Both tolerances of Termination::converged met.
+    Converged,
+    TimeLimit,
+    // No improvement for too long and no restarts left (see RestartPolicy).
+    Stagnated,
+    Degenerate,
+    ObserverStopped,
+    Cancelled,
+}
+
+impl Termination{
+    // No criteria at all. Set at least one before running or the run never stops.
+    pub fn new() -> Termination{
+        Termination{
+            max_iterations: None,
+            max_evaluations: None,
+            f_tol: None,
+            x_tol: None,
+            converged: None,
+            time_limit: None,
+            cancellation: None
+        }
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Termination{
+        self.max_iterations = Some(max_iterations);
+        self
+    }
+
+    pub fn max_evaluations(mut self, max_evaluations: usize) -> Termination{
+        self.max_evaluations = Some(max_evaluations);
+        self
+    }
+
+    pub fn f_tol(mut self, f_tol: f64) -> Termination{
+        self.f_tol = Some(f_tol);
+        self
+    }
+
+    pub fn x_tol(mut self, x_tol: f64) -> Termination{
+        self.x_tol = Some(x_tol);
+        self
+    }
+
+    pub fn converged(mut self, f_tol: f64, x_tol: f64) -> Termination{
+        self.converged = Some((f_tol, x_tol));
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Termination{
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Termination{
+        self.cancellation = Some(token);
+        self
+    }
+
+    // iterations and evaluations are counted from the start of the run. The diameter takes
+    // O(n^3) operations, so it is only computed when a criterion can use it.
+    pub fn check<S: Vertices + ?Sized>(&self, simplex: &S, iterations: usize, evaluations: usize, start: Instant) -> Option<StopReason>{
+        let f_spread = if self.f_tol.is_some() || self.converged.is_some() { simplex.f_spread() } else { cast(f64::INFINITY) };
+        let needs_size = self.x_tol.is_some() || self.converged.is_some_and(|(f_tol, _)| f_spread <= cast(f_tol));
+        let size = if needs_size { simplex.diameter() } else { cast(f64::INFINITY) };
+        self.check_progress(f_spread, size, iterations, evaluations, start)
+    }
+
+    // check() for optimizers without a simplex: f_spread is compared with f_tol and size with x_tol.
+    pub fn check_progress<T: Real>(&self, f_spread: T, size: T, iterations: usize, evaluations: usize, start: Instant) -> Option<StopReason>{
+        if let Some(token) = &self.cancellation{
+            if token.is_cancelled(){
+                return Some(StopReason::Cancelled);
+            }
+        }
+        if let Some(f_tol) = self.f_tol{
+            if f_spread <= cast(f_tol){
+                return Some(StopReason::FunctionTolerance);
+            }
+        }
+        if let Some(x_tol) = self.x_tol{
+            if size <= cast(x_tol){
+                return Some(StopReason::StepTolerance);
+            }
+        }
+        if let Some((f_tol, x_tol)) = self.converged{
+            if f_spread <= cast(f_tol) && size <= cast(x_tol){
+                return Some(StopReason::Converged);
+            }
+        }
+        self.check_budget(iterations, evaluations, start)
+    }
+
+    // Only the limits on iterations, evaluations and time, for optimizers that treat the
+    // tolerances differently (see CmaEs).
+    pub fn check_budget(&self, iterations: usize, evaluations: usize, start: Instant) -> Option<StopReason>{
+        if let Some(max_iterations) = self.max_iterations{
+            if iterations >= max_iterations{
+                return Some(StopReason::MaxIterations);
+            }
+        }
+        // Checked between iterations, and an iteration can take several evaluations (n for a
+        // shrink), so a run can overshoot this.
+        if let Some(max_evaluations) = self.max_evaluations{
+            if evaluations >= max_evaluations{
+                return Some(StopReason::MaxEvaluations);
+            }
+        }
+        if let Some(time_limit) = self.time_limit{
+            if start.elapsed() >= time_limit{
+                return Some(StopReason::TimeLimit);
+            }
+        }
+        None
+    }
+}
+
+impl Default for Termination{
+    fn default() -> Termination{
+        Termination::new().max_iterations(10_000).f_tol(1e-10)
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        let representation = match self{
+            StopReason::MaxIterations => "maximum number of iterations reached",
+            StopReason::MaxEvaluations => "maximum number of function evaluations reached",
+            StopReason::FunctionTolerance => "spread of function values below tolerance",
+            StopReason::StepTolerance => "simplex diameter or step size below tolerance",
+            StopReason::Converged => "spread of function values and simplex diameter below tolerance",
+            StopReason::TimeLimit => "time limit reached",
+            StopReason::Stagnated => "no improvement and no restarts left",
+            StopReason::Degenerate => "simplex degenerated and no restarts left",
+            StopReason::ObserverStopped => "stopped by observer",
+            StopReason::Cancelled => "cancelled"
+        };
+        write!(f, "{}", representation)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::simplex::Simplex;
+
+    fn level_simplex() -> Simplex{
+        // every vertex at the same distance from the origin, so the values are equal
+        let mut sphere = |x: &[f64]| x.iter().map(|xi| xi * xi).sum::<f64>();
+        Simplex::new(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![-1.0, 0.0]], &mut sphere).unwrap()
+    }
+
+    #[test]
+    fn converged_needs_both_tolerances(){
+        let simplex = level_simplex();
+        let start = Instant::now();
+        assert_eq!(Termination::new().f_tol(1e-10).check(&simplex, 0, 3, start), Some(StopReason::FunctionTolerance));
+        assert_eq!(Termination::new().converged(1e-10, 1e-8).check(&simplex, 0, 3, start), None);
+        assert_eq!(Termination::new().converged(1e-10, 2.0).check(&simplex, 0, 3, start), Some(StopReason::Converged));
+    }
+
+    #[test]
+    fn checks_only_the_criteria_that_are_set(){
+        let simplex = level_simplex();
+        let start = Instant::now();
+        assert_eq!(Termination::new().check(&simplex, 1_000_000, 1_000_000, start), None);
+        assert_eq!(Termination::new().max_iterations(10).check(&simplex, 10, 0, start), Some(StopReason::MaxIterations));
+        assert_eq!(Termination::new().max_evaluations(10).check(&simplex, 0, 9, start), None);
+        assert_eq!(Termination::new().x_tol(2.0).check(&simplex, 0, 0, start), Some(StopReason::StepTolerance));
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(Termination::new().cancellation(token).check(&simplex, 0, 0, start), Some(StopReason::Cancelled));
+    }
+}